@@ -142,6 +142,24 @@ CREATE TABLE ad.study_iec(
 CREATE INDEX study_iec_sid ON ad.study_iec(sd_sid);
 
 
+DROP TABLE IF EXISTS ad.study_iec_constraints;
+CREATE TABLE ad.study_iec_constraints(
+  id                     INT             PRIMARY KEY GENERATED ALWAYS AS IDENTITY (start with 10000001 increment by 1)
+, sd_sid                 VARCHAR         NOT NULL
+, seq_num                INT             NULL
+, ie_type_id             INT             NULL
+, constraint_type        VARCHAR         NULL
+, parameter              VARCHAR         NULL
+, comparator             VARCHAR         NULL
+, value_low              float           NULL
+, value_high             float           NULL
+, units                  VARCHAR         NULL
+, negated                BOOLEAN         NOT NULL default false
+, added_on               TIMESTAMPTZ     NOT NULL default now()
+);
+CREATE INDEX study_iec_constraints_sid ON ad.study_iec_constraints(sd_sid);
+
+
 DROP TABLE IF EXISTS ad.study_countries;
 CREATE TABLE ad.study_countries(
   id                     INT             PRIMARY KEY GENERATED ALWAYS AS IDENTITY (start with 10000001 increment by 1)
@@ -150,7 +168,7 @@ CREATE TABLE ad.study_countries(
 , country_name           VARCHAR         NULL
 , status_id              INT             NULL
 , added_on               TIMESTAMPTZ     NOT NULL default now()
-, coded_on               TIMESTAMPTZ     NULL  default now()       -- already coded when added
+, coded_on               TIMESTAMPTZ     NULL
 );
 CREATE INDEX study_countries_sid ON ad.study_countries(sd_sid);
 
@@ -253,10 +271,6 @@ CREATE TABLE ad.study_pub_instances(
 CREATE INDEX study_pubs_insts_sid ON ad.study_pub_instances(sd_sid);
 
 
-SET client_min_messages TO NOTICE;
-
-
-/*
 DROP TABLE IF EXISTS ad.study_locations;
 CREATE TABLE ad.study_locations(
   id                     INT             PRIMARY KEY GENERATED ALWAYS AS IDENTITY (start with 10000001 increment by 1)
@@ -264,6 +278,7 @@ CREATE TABLE ad.study_locations(
 , fac_id                 INT             NULL
 , fac_name               VARCHAR         NULL
 , fac_ror_id             VARCHAR         NULL
+, fac_address            VARCHAR         NULL
 , city_id                INT             NULL
 , city_name              VARCHAR         NULL
 , disamb_id              INT             NULL
@@ -275,4 +290,34 @@ CREATE TABLE ad.study_locations(
 , coded_on               TIMESTAMPTZ     NULL
 );
 CREATE INDEX study_locations_sid ON ad.study_locations(sd_sid);
-*/
+
+
+DROP TABLE IF EXISTS ad.study_ethics;
+CREATE TABLE ad.study_ethics(
+  id                     INT             PRIMARY KEY GENERATED ALWAYS AS IDENTITY (start with 10000001 increment by 1)
+, sd_sid                 VARCHAR         NOT NULL
+, approval_required      VARCHAR         NULL
+, approval_notes         VARCHAR         NULL
+, committee_name         VARCHAR         NULL
+, approval_status        VARCHAR         NULL
+, status_date            DATE            NULL
+, committee_ref          VARCHAR         NULL
+, added_on               TIMESTAMPTZ     NOT NULL default now()
+);
+CREATE INDEX study_ethics_sid ON ad.study_ethics(sd_sid);
+
+
+DROP TABLE IF EXISTS ad.study_outcomes;
+CREATE TABLE ad.study_outcomes(
+  id                     INT             PRIMARY KEY GENERATED ALWAYS AS IDENTITY (start with 10000001 increment by 1)
+, sd_sid                 VARCHAR         NOT NULL
+, outcome_type           VARCHAR         NOT NULL
+, variable               VARCHAR         NULL
+, method                 VARCHAR         NULL
+, timepoints             VARCHAR         NULL
+, added_on               TIMESTAMPTZ     NOT NULL default now()
+);
+CREATE INDEX study_outcomes_sid ON ad.study_outcomes(sd_sid);
+
+
+SET client_min_messages TO NOTICE;
@@ -0,0 +1,35 @@
+SET client_min_messages TO WARNING;
+
+-- The tables and columns added to the event tables (evs) of the monitor database by
+-- this program. The schema, and the download and import event tables, are created
+-- elsewhere, with the other MDR databases. Every statement can be rerun, and the script
+-- is run once at the start of each run of the program, so that the tables are always current.
+
+
+-- The end of the last completed period of a download, from which it can be resumed.
+
+alter table evs.dl_events add column if not exists checkpoint_date date null;
+
+
+-- The number of studies downloaded again whose content was unchanged.
+
+alter table evs.dl_events add column if not exists num_records_unchanged int null;
+
+
+-- The number of json files skipped (quarantined) during an import.
+
+alter table evs.imp_events add column if not exists num_records_skipped int null;
+
+
+-- Coding events (-e / -E), recorded in the same way as downloads and imports.
+
+create table if not exists evs.coding_events (
+    id                      int          not null primary key
+  , source_id               int          not null
+  , coding_type             varchar      null
+  , time_started            timestamptz  null
+  , time_ended              timestamptz  null
+  , num_orgs_coded          int          null
+  , num_countries_coded     int          null
+  , num_conditions_coded    int          null
+);
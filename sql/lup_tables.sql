@@ -0,0 +1,27 @@
+SET client_min_messages TO WARNING;
+create schema if not exists lup;
+
+
+DROP TABLE IF EXISTS lup.org_names;
+CREATE TABLE lup.org_names(
+  org_id                 VARCHAR         NOT NULL
+, org_name               VARCHAR         NOT NULL
+);
+CREATE INDEX org_names_name ON lup.org_names(org_name);
+
+
+DROP TABLE IF EXISTS lup.country_names;
+CREATE TABLE lup.country_names(
+  country_id             INT             NOT NULL
+, country_name           VARCHAR         NOT NULL
+);
+CREATE INDEX country_names_name ON lup.country_names(country_name);
+
+
+DROP TABLE IF EXISTS lup.condition_names;
+CREATE TABLE lup.condition_names(
+  icd_code               VARCHAR         NOT NULL
+, icd_name               VARCHAR         NULL
+, condition_name         VARCHAR         NOT NULL
+);
+CREATE INDEX condition_names_name ON lup.condition_names(condition_name);
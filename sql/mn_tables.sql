@@ -0,0 +1,86 @@
+SET client_min_messages TO WARNING;
+
+-- The tables and columns added to the monitor (mn) schema by this program. The
+-- schema and mn.source_data itself are created elsewhere, with the other MDR
+-- source databases. Every statement can be rerun, and the script is run once
+-- at the start of each run of the program, so that the tables are always current.
+
+
+-- A single record for each study, as required by the upsert of each page of downloaded
+-- records. Any duplicate records (which would stop the index being created) are first
+-- removed, keeping the most recently downloaded one for each study. The index is
+-- not added if the table already has one (e.g. a test table copied from mn.source_data).
+
+delete from mn.source_data s
+using (select id, row_number() over (partition by sd_sid
+                  order by last_downloaded desc nulls last, id desc) as rn
+       from mn.source_data where sd_sid is not null) d
+where s.id = d.id and d.rn > 1;
+do $$
+begin
+    if not exists (select 1 from pg_index i
+                   inner join pg_attribute a on a.attrelid = i.indrelid and a.attnum = i.indkey[0]
+                   where i.indrelid = 'mn.source_data'::regclass and i.indisunique
+                   and i.indnkeyatts = 1 and a.attname = 'sd_sid') then
+        create unique index source_data_sd_sid on mn.source_data(sd_sid);
+    end if;
+end $$;
+
+
+-- Hash of each study's json content, and the study's status in the registry
+-- (set by a reconciliation - a null status is equivalent to 'current').
+
+alter table mn.source_data add column if not exists content_hash varchar null;
+alter table mn.source_data add column if not exists registry_status varchar null;
+alter table mn.source_data add column if not exists status_date date null;
+
+
+-- The last coding event in which any of a study's organisations, countries or
+-- conditions were coded, and when.
+
+alter table mn.source_data add column if not exists last_coding_id int null;
+alter table mn.source_data add column if not exists last_coded timestamptz null;
+
+
+-- Changes found in the fields of studies downloaded again.
+
+create table if not exists mn.study_changes (
+    id                 int         GENERATED ALWAYS AS IDENTITY PRIMARY KEY
+  , sd_sid             varchar     not null
+  , dl_id              int         not null
+  , field_path         varchar     not null
+  , old_value          varchar     null
+  , new_value          varchar     null
+  , detected_on        timestamptz not null default now()
+);
+create index if not exists study_changes_dl_id on mn.study_changes(dl_id);
+
+
+-- Each distinct version of a study (--archive), held once, as jsonb, keyed by the
+-- study id and the hash of its content, with the registry's version and last updated date.
+
+create table if not exists mn.source_data_history (
+    id                 int         GENERATED ALWAYS AS IDENTITY PRIMARY KEY
+  , sd_sid             varchar     not null
+  , record_version     varchar     null
+  , last_updated       timestamp   null
+  , content_hash       varchar     not null
+  , study_json         jsonb       not null
+  , dl_id              int         not null
+  , archived_on        timestamptz not null default now()
+);
+create unique index if not exists source_data_history_hash on mn.source_data_history(sd_sid, content_hash);
+create index if not exists source_data_history_updated on mn.source_data_history(sd_sid, last_updated);
+
+
+-- The quarantine list of json files that could not be imported.
+
+create table if not exists mn.import_failures (
+    id                 int         GENERATED ALWAYS AS IDENTITY PRIMARY KEY
+  , sd_sid             varchar     not null
+  , local_path         varchar     null
+  , error              varchar     null
+  , imp_event_id       int         not null
+  , failed_on          timestamptz not null default now()
+);
+create index if not exists import_failures_event on mn.import_failures(imp_event_id);
@@ -141,6 +141,24 @@ CREATE TABLE sd.study_iec(
 CREATE INDEX study_iec_sid ON sd.study_iec(sd_sid);
 
 
+DROP TABLE IF EXISTS sd.study_iec_constraints;
+CREATE TABLE sd.study_iec_constraints(
+  id                     INT             PRIMARY KEY GENERATED ALWAYS AS IDENTITY (start with 10000001 increment by 1)
+, sd_sid                 VARCHAR         NOT NULL
+, seq_num                INT             NULL
+, ie_type_id             INT             NULL
+, constraint_type        VARCHAR         NULL
+, parameter              VARCHAR         NULL
+, comparator             VARCHAR         NULL
+, value_low              float           NULL
+, value_high             float           NULL
+, units                  VARCHAR         NULL
+, negated                BOOLEAN         NOT NULL default false
+, added_on               TIMESTAMPTZ     NOT NULL default now()
+);
+CREATE INDEX study_iec_constraints_sid ON sd.study_iec_constraints(sd_sid);
+
+
 DROP TABLE IF EXISTS sd.study_countries;
 CREATE TABLE sd.study_countries(
   id                     INT             PRIMARY KEY GENERATED ALWAYS AS IDENTITY (start with 10000001 increment by 1)
@@ -242,12 +260,6 @@ CREATE TABLE sd.study_pub_instances(
 );
 CREATE INDEX study_pubs_insts_sid ON sd.study_pub_instances(sd_sid);
 
-SET client_min_messages TO NOTICE;
-
-
-/*
-
--- change of names to facility and address
 
 DROP TABLE IF EXISTS sd.study_locations;
 CREATE TABLE sd.study_locations(
@@ -261,4 +273,33 @@ CREATE TABLE sd.study_locations(
 , added_on               TIMESTAMPTZ     NOT NULL default now()
 );
 CREATE INDEX study_locations_sid ON sd.study_locations(sd_sid);
-*/
+
+
+DROP TABLE IF EXISTS sd.study_ethics;
+CREATE TABLE sd.study_ethics(
+  id                     INT             PRIMARY KEY GENERATED ALWAYS AS IDENTITY (start with 10000001 increment by 1)
+, sd_sid                 VARCHAR         NOT NULL
+, approval_required      VARCHAR         NULL
+, approval_notes         VARCHAR         NULL
+, committee_name         VARCHAR         NULL
+, approval_status        VARCHAR         NULL
+, status_date            DATE            NULL
+, committee_ref          VARCHAR         NULL
+, added_on               TIMESTAMPTZ     NOT NULL default now()
+);
+CREATE INDEX study_ethics_sid ON sd.study_ethics(sd_sid);
+
+
+DROP TABLE IF EXISTS sd.study_outcomes;
+CREATE TABLE sd.study_outcomes(
+  id                     INT             PRIMARY KEY GENERATED ALWAYS AS IDENTITY (start with 10000001 increment by 1)
+, sd_sid                 VARCHAR         NOT NULL
+, outcome_type           VARCHAR         NOT NULL
+, variable               VARCHAR         NULL
+, method                 VARCHAR         NULL
+, timepoints             VARCHAR         NULL
+, added_on               TIMESTAMPTZ     NOT NULL default now()
+);
+CREATE INDEX study_outcomes_sid ON sd.study_outcomes(sd_sid);
+
+SET client_min_messages TO NOTICE;
@@ -1,11 +1,13 @@
 use std::ops::Add;
 use chrono::NaiveDate;
 use std::path::PathBuf;
+use crate::setup::config_reader::ApiPars;
 
 pub struct InitParams {
     pub source_id: i32,
     pub source_name: String,
     pub api_base_url: String,
+    pub api_pars: ApiPars,
     pub json_data_path: PathBuf,
     pub log_folder_path: PathBuf,
     pub download_type: DownloadType,
@@ -13,6 +15,14 @@ pub struct InitParams {
     pub encoding_type: EncodingType,
     pub start_date: Option<NaiveDate>,
     pub end_date: Option<NaiveDate>,
+    pub save_xml_path: Option<PathBuf>,
+    pub from_xml_path: Option<PathBuf>,
+    pub resume_id: Option<i32>,
+    pub archive: bool,
+    pub as_of_query: Option<(String, NaiveDate)>,
+    pub changes_dl_id: Option<i32>,
+    pub change_fields: Vec<String>,
+    pub exclude_missing: bool,
     pub is_test: bool,
 }
 
@@ -22,6 +32,8 @@ pub enum DownloadType {
     UdBetweenDates,
     CrBetweenDates,
     ByYear,
+    FromXml,
+    Reconcile,
     None
 }
 
@@ -32,9 +44,21 @@ impl DownloadType {
             DownloadType::UdBetweenDates => "Updated between dates".to_string(),
             DownloadType::CrBetweenDates => "Created between dates".to_string(),
             DownloadType::ByYear => "Created in year".to_string(),
+            DownloadType::FromXml => "Replayed from saved xml".to_string(),
+            DownloadType::Reconcile => "Reconciliation of ids".to_string(),
             DownloadType::None => "None".to_string(),
         }
     }
+
+    pub fn from_event_string(s: &str) -> Option<Self> {
+        match s {
+            "Recently updated" => Some(DownloadType::Recent),
+            "Updated between dates" => Some(DownloadType::UdBetweenDates),
+            "Created between dates" => Some(DownloadType::CrBetweenDates),
+            "Created in year" => Some(DownloadType::ByYear),
+            _ => None,   // other types cannot be resumed
+        }
+    }
 }
 
 
@@ -79,6 +103,7 @@ pub struct DownloadResult {
     pub num_checked: i32,
     pub num_downloaded: i32,
     pub num_added: i32,
+    pub num_unchanged: i32,
 }
 
 impl DownloadResult {
@@ -87,6 +112,7 @@ impl DownloadResult {
         num_checked: 0,
         num_downloaded: 0,
         num_added: 0,
+        num_unchanged: 0,
         }
    }
 }
@@ -99,14 +125,32 @@ impl Add for DownloadResult {
             num_checked: self.num_checked + other.num_checked,
             num_downloaded: self.num_downloaded + other.num_downloaded,
             num_added: self.num_added + other.num_added,
+            num_unchanged: self.num_unchanged + other.num_unchanged,
         }
     }
 }
 
 
+pub struct DownloadCheckpoint {
+    pub dl_type: DownloadType,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    pub checkpoint_date: Option<NaiveDate>,
+    pub res: DownloadResult,
+}
+
+
 pub struct ImportResult {
     pub num_available: i64,
     pub num_imported: i64,
-    pub earliest_dl_date: NaiveDate,
-    pub latest_dl_date: NaiveDate,
+    pub num_skipped: i64,
+    pub earliest_dl_date: Option<NaiveDate>,
+    pub latest_dl_date: Option<NaiveDate>,
+}
+
+
+pub struct CodingResult {
+    pub num_orgs_coded: i64,
+    pub num_countries_coded: i64,
+    pub num_conditions_coded: i64,
 }
@@ -0,0 +1,141 @@
+use crate::AppError;
+use crate::base_types::EncodingType;
+use crate::setup::test_run::schema_sql;
+use sqlx::{Pool, Postgres};
+
+async fn execute_update(sql: &str, src_pool: &Pool<Postgres>) -> Result<i64, AppError> {
+
+    let sql = &schema_sql(sql);
+    let res = sqlx::raw_sql(sql)
+        .execute(src_pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    Ok(res.rows_affected() as i64)
+}
+
+// For a full recoding any existing codes are cleared first. For a recent
+// recoding only rows that have not yet been coded are considered - which will
+// include any that failed to match in a previous run, as coded_on is only set
+// when a match is found, and the lookup tables may since have been extended.
+
+fn uncoded_clause(encoding_type: &EncodingType) -> &'static str {
+    match encoding_type {
+        EncodingType::Recent => "and g.coded_on is null",
+        _ => "",
+    }
+}
+
+
+pub async fn code_study_orgs(encoding_type: &EncodingType, src_pool: &Pool<Postgres>) -> Result<i64, AppError> {
+
+    if *encoding_type == EncodingType::All {
+        execute_update(&clear_codes_sql("study_orgs", "org_id = null"), src_pool).await?;
+    }
+
+    // Where a name maps to more than one organisation the lowest id is used. The ids are
+    // held as text, so are compared as numbers to find it.
+
+    let sql = coding_sql("study_orgs", "org_id = n.org_id", "org_name", "org_names",
+                         "org_name, org_id", "org_name, org_id::int", encoding_type);
+    execute_update(&sql, src_pool).await
+}
+
+
+pub async fn code_study_countries(encoding_type: &EncodingType, src_pool: &Pool<Postgres>) -> Result<i64, AppError> {
+
+    if *encoding_type == EncodingType::All {
+        execute_update(&clear_codes_sql("study_countries", "country_id = null"), src_pool).await?;
+    }
+
+    let sql = coding_sql("study_countries", "country_id = n.country_id", "country_name", "country_names",
+                         "country_name, country_id", "country_name, country_id", encoding_type);
+    execute_update(&sql, src_pool).await
+}
+
+
+pub async fn code_study_conditions(encoding_type: &EncodingType, src_pool: &Pool<Postgres>) -> Result<i64, AppError> {
+
+    if *encoding_type == EncodingType::All {
+        execute_update(&clear_codes_sql("study_conditions", "icd_code = null, icd_name = null"), src_pool).await?;
+    }
+
+    let sql = coding_sql("study_conditions", "icd_code = n.icd_code, icd_name = n.icd_name", "original_value",
+                         "condition_names", "condition_name, icd_code, icd_name",
+                         "condition_name, icd_code, icd_name", encoding_type);
+    execute_update(&sql, src_pool).await
+}
+
+
+fn clear_codes_sql(table: &str, code_columns: &str) -> String {
+    format!("update ad.{} set {}, coded_on = null", table, code_columns)
+}
+
+
+fn coding_sql(table: &str, code_columns: &str, name_column: &str, lookup_table: &str,
+              lookup_columns: &str, order_columns: &str, encoding_type: &EncodingType) -> String {
+
+    // The lookup is reduced to one row per name (the first lookup column), the ordering
+    // (by the name, then the remaining order columns) choosing the row with the lowest code.
+
+    let lookup_name = lookup_columns.split(',').next().unwrap_or(lookup_columns).trim();
+    format!(r#"update ad.{table} g
+                set {code_columns},
+                coded_on = now()
+                from (select distinct on ({lookup_name}) {lookup_columns}
+                      from lup.{lookup_table}
+                      order by {order_columns}) n
+                where lower(trim(g.{name_column})) = n.{lookup_name}
+                {}"#, uncoded_clause(encoding_type))
+}
+
+
+pub async fn drop_lookup_tables(src_pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    let sql = r#"SET client_min_messages TO WARNING;
+                DROP SCHEMA IF EXISTS lup CASCADE;"#;
+    execute_update(sql, src_pool).await?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalised(sql: &str) -> String {
+        sql.split_whitespace().collect::<Vec<&str>>().join(" ")
+    }
+
+    #[test]
+    fn check_org_coding_sql() {
+        let sql = coding_sql("study_orgs", "org_id = n.org_id", "org_name", "org_names",
+                             "org_name, org_id", "org_name, org_id::int", &EncodingType::All);
+        assert_eq!(normalised(&sql), "update ad.study_orgs g set org_id = n.org_id, coded_on = now() \
+            from (select distinct on (org_name) org_name, org_id from lup.org_names order by org_name, org_id::int) n \
+            where lower(trim(g.org_name)) = n.org_name");
+    }
+
+    #[test]
+    fn check_recent_coding_restricted_to_uncoded_rows() {
+        let sql = coding_sql("study_countries", "country_id = n.country_id", "country_name", "country_names",
+                             "country_name, country_id", "country_name, country_id", &EncodingType::Recent);
+        assert!(normalised(&sql).ends_with("where lower(trim(g.country_name)) = n.country_name and g.coded_on is null"));
+    }
+
+    #[test]
+    fn check_condition_coding_sql() {
+        let sql = coding_sql("study_conditions", "icd_code = n.icd_code, icd_name = n.icd_name", "original_value",
+                             "condition_names", "condition_name, icd_code, icd_name",
+                             "condition_name, icd_code, icd_name", &EncodingType::All);
+        let sql = normalised(&sql);
+        assert!(sql.starts_with("update ad.study_conditions g set icd_code = n.icd_code, icd_name = n.icd_name, coded_on = now()"));
+        assert!(sql.contains("select distinct on (condition_name) condition_name, icd_code, icd_name from lup.condition_names"));
+        assert!(sql.contains("where lower(trim(g.original_value)) = n.condition_name"));
+    }
+
+    #[test]
+    fn check_codes_cleared_for_full_recoding() {
+        assert_eq!(clear_codes_sql("study_conditions", "icd_code = null, icd_name = null"),
+                   "update ad.study_conditions set icd_code = null, icd_name = null, coded_on = null");
+    }
+}
@@ -0,0 +1,124 @@
+use crate::AppError;
+use crate::setup::test_run::schema_sql;
+use sqlx::{Pool, Postgres, postgres::PgQueryResult};
+
+// The lookup tables are held in the context database. Rather than matching
+// across databases the relevant rows are read into memory and then written
+// into a 'lup' schema in the source database, where the coding updates can
+// join against them. Names are lower cased and trimmed on the way in, so that
+// the matching in the coding SQL only has to normalise the ad side.
+
+#[derive(sqlx::FromRow)]
+struct OrgName {
+    org_id: String,
+    org_name: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct CountryName {
+    country_id: i32,
+    country_name: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct ConditionName {
+    icd_code: String,
+    icd_name: Option<String>,
+    condition_name: String,
+}
+
+
+pub async fn load_lookup_tables(cxt_pool: &Pool<Postgres>, src_pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    let sql = &schema_sql(include_str!("../../sql/lup_tables.sql"));
+    sqlx::raw_sql(sql).execute(src_pool)
+        .await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    load_org_names(cxt_pool, src_pool).await?;
+    load_country_names(cxt_pool, src_pool).await?;
+    load_condition_names(cxt_pool, src_pool).await?;
+
+    Ok(())
+}
+
+
+async fn load_org_names(cxt_pool: &Pool<Postgres>, src_pool: &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
+
+    let sql = r#"select distinct org_id::varchar as org_id, lower(trim(name)) as org_name
+                from ctx.org_names
+                where name is not null"#;
+    let rows: Vec<OrgName> = sqlx::query_as(sql).fetch_all(cxt_pool).await
+                    .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    let mut org_ids: Vec<String> = Vec::with_capacity(rows.len());
+    let mut org_names: Vec<String> = Vec::with_capacity(rows.len());
+    for r in rows {
+        org_ids.push(r.org_id);
+        org_names.push(r.org_name);
+    }
+
+    let sql = &schema_sql(r#"INSERT INTO lup.org_names (org_id, org_name)
+            SELECT * FROM UNNEST($1::text[], $2::text[])"#);
+
+    sqlx::query(sql)
+    .bind(&org_ids)
+    .bind(&org_names)
+    .execute(src_pool)
+    .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))
+}
+
+
+async fn load_country_names(cxt_pool: &Pool<Postgres>, src_pool: &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
+
+    let sql = r#"select distinct country_id, lower(trim(alt_name)) as country_name
+                from ctx.country_names
+                where alt_name is not null"#;
+    let rows: Vec<CountryName> = sqlx::query_as(sql).fetch_all(cxt_pool).await
+                    .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    let mut country_ids: Vec<i32> = Vec::with_capacity(rows.len());
+    let mut country_names: Vec<String> = Vec::with_capacity(rows.len());
+    for r in rows {
+        country_ids.push(r.country_id);
+        country_names.push(r.country_name);
+    }
+
+    let sql = &schema_sql(r#"INSERT INTO lup.country_names (country_id, country_name)
+            SELECT * FROM UNNEST($1::int[], $2::text[])"#);
+
+    sqlx::query(sql)
+    .bind(&country_ids)
+    .bind(&country_names)
+    .execute(src_pool)
+    .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))
+}
+
+
+async fn load_condition_names(cxt_pool: &Pool<Postgres>, src_pool: &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
+
+    let sql = r#"select distinct icd_code, icd_name, lower(trim(condition_name)) as condition_name
+                from ctx.condition_names
+                where condition_name is not null"#;
+    let rows: Vec<ConditionName> = sqlx::query_as(sql).fetch_all(cxt_pool).await
+                    .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    let mut icd_codes: Vec<String> = Vec::with_capacity(rows.len());
+    let mut icd_names: Vec<Option<String>> = Vec::with_capacity(rows.len());
+    let mut condition_names: Vec<String> = Vec::with_capacity(rows.len());
+    for r in rows {
+        icd_codes.push(r.icd_code);
+        icd_names.push(r.icd_name);
+        condition_names.push(r.condition_name);
+    }
+
+    let sql = &schema_sql(r#"INSERT INTO lup.condition_names (icd_code, icd_name, condition_name)
+            SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[])"#);
+
+    sqlx::query(sql)
+    .bind(&icd_codes)
+    .bind(&icd_names)
+    .bind(&condition_names)
+    .execute(src_pool)
+    .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))
+}
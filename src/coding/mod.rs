@@ -0,0 +1,53 @@
+mod lookups;
+mod coders;
+
+use crate::setup::db_pars::get_db_pool;
+use crate::AppError;
+use crate::base_types::{EncodingType, CodingResult};
+use crate::recording::processes::MonitorRepo;
+use coders::*;
+use chrono::{DateTime, Utc};
+use log::info;
+
+pub async fn code_data(encoding_type: &EncodingType, coding_event_id: i32) -> Result<CodingResult, AppError> {
+
+    let src_pool = &get_db_pool("source").await?;   // pool for the source specific db
+    let cxt_pool = &get_db_pool("context").await?;  // pool for the context db
+
+    // The start time is taken from the source database, as it is compared with the
+    // times recorded there (by now()) as rows are coded.
+
+    let sql = "select now()";
+    let coding_started: DateTime<Utc> = sqlx::query_scalar(sql).fetch_one(src_pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    // Bring the lookup tables over from the context database.
+
+    lookups::load_lookup_tables(cxt_pool, src_pool).await?;
+    info!("Lookup tables loaded from context database");
+
+    // Code each of the relevant ad tables in turn.
+
+    let num_orgs_coded = code_study_orgs(encoding_type, src_pool).await?;
+    info!("{} organisation records coded", num_orgs_coded);
+
+    let num_countries_coded = code_study_countries(encoding_type, src_pool).await?;
+    info!("{} country records coded", num_countries_coded);
+
+    let num_conditions_coded = code_study_conditions(encoding_type, src_pool).await?;
+    info!("{} condition records coded", num_conditions_coded);
+
+    // Record the event against each study that has had anything coded.
+
+    let num_studies = MonitorRepo::new(src_pool.clone()).update_coding_details(coding_event_id, coding_started).await?;
+    info!("{} studies recorded as coded in mn.source_data", num_studies);
+
+    drop_lookup_tables(src_pool).await?;
+    info!("Coding event {} completed", coding_event_id);
+
+    Ok(CodingResult {
+        num_orgs_coded,
+        num_countries_coded,
+        num_conditions_coded,
+    })
+}
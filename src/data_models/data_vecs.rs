@@ -1,6 +1,7 @@
 use crate::data_models::db_models::*;
-use crate::iec::iec_structs::IECLine;
+use crate::iec::iec_structs::{IECLine, IECConstraint};
 use crate::AppError;
+use crate::setup::test_run::schema_sql;
 use sqlx::{Pool, Postgres, postgres::PgQueryResult};
 use chrono::{NaiveDate, NaiveDateTime};
 
@@ -53,10 +54,10 @@ impl StudyVecs{
 
     pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
 
-        let sql = r#"INSERT INTO sd.studies (sd_sid, display_title, brief_description, type_id, status_id, status_override, start_status_override,
+        let sql = &schema_sql(r#"INSERT INTO sd.studies (sd_sid, display_title, brief_description, type_id, status_id, status_override, start_status_override,
                         is_ipd_sharing, ipd_sharing_plan, date_last_revised, dt_of_data_fetch)
                         SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::int[], $5::int[], $6::text[], $7::text[],
-                        $8::bool[], $9::text[], $10::date[], $11::timestamp[])"#;
+                        $8::bool[], $9::text[], $10::date[], $11::timestamp[])"#);
 
         sqlx::query(sql)
         .bind(&self.sd_sids)
@@ -130,10 +131,10 @@ impl StudyDatesVecs{
 
     pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
 
-        let sql = r#"INSERT INTO sd.study_dates (sd_sid, reg_year, reg_month, reg_date_type, start_year, start_month, start_date_type,
+        let sql = &schema_sql(r#"INSERT INTO sd.study_dates (sd_sid, reg_year, reg_month, reg_date_type, start_year, start_month, start_date_type,
                         comp_year, comp_month, comp_date_type, res_year, res_month, res_date_type)
             SELECT * FROM UNNEST($1::text[], $2::int[], $3::int[], $4::text[], $5::int[], $6::int[], $7::text[],
-                                 $8::int[], $9::int[], $10::text[], $11::int[], $12::int[], $13::text[])"#;
+                                 $8::int[], $9::int[], $10::text[], $11::int[], $12::int[], $13::text[])"#);
 
         sqlx::query(sql)
         .bind(&self.sd_sids)
@@ -218,14 +219,14 @@ impl StudyParticsVecs{
 
     pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
 
-        let sql = r#"INSERT INTO sd.study_participants (sd_sid, enrolment_target, enrolment_final,
+        let sql = &schema_sql(r#"INSERT INTO sd.study_participants (sd_sid, enrolment_target, enrolment_final,
                 enrolment_total, enrolment, enrolment_type, gender_string, gender_flag,
                 min_age_string, min_age, min_age_units_id,
                 max_age_string, max_age, max_age_units_id, age_group_flag, iec_flag)
             SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[], $5::text[], $6::text[],
                     $7::text[], $8::text[], $9::text[],
                     $10::float[], $11::text[], $12::text[], $13::float[], $14::text[],
-                    $15::int[], $16::int[])"#;
+                    $15::int[], $16::int[])"#);
 
         sqlx::query(sql)
         .bind(&self.sd_sids)
@@ -299,8 +300,8 @@ impl TitleVecs{
 
     pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
 
-        let sql = r#"INSERT INTO sd.study_titles (sd_sid, title_text, is_public, is_scientific, is_acronym, is_display, comments)
-            SELECT * FROM UNNEST($1::text[], $2::text[], $3::bool[], $4::bool[], $5::bool[], $6::bool[], $7::text[])"#;
+        let sql = &schema_sql(r#"INSERT INTO sd.study_titles (sd_sid, title_text, is_public, is_scientific, is_acronym, is_display, comments)
+            SELECT * FROM UNNEST($1::text[], $2::text[], $3::bool[], $4::bool[], $5::bool[], $6::bool[], $7::text[])"#);
 
         sqlx::query(sql)
         .bind(&self.sd_sids)
@@ -352,8 +353,8 @@ impl IdentifierVecs{
 
     pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
 
-        let sql = r#"INSERT INTO sd.study_identifiers (sd_sid, id_value, id_type_id, id_type)
-            SELECT * FROM UNNEST($1::text[], $2::text[], $3::int[], $4::text[])"#;
+        let sql = &schema_sql(r#"INSERT INTO sd.study_identifiers (sd_sid, id_value, id_type_id, id_type)
+            SELECT * FROM UNNEST($1::text[], $2::text[], $3::int[], $4::text[])"#);
 
         sqlx::query(sql)
         .bind(&self.sd_sids)
@@ -419,10 +420,10 @@ impl OrgVecs{
 
     pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
 
-        let sql = r#"INSERT INTO sd.study_orgs (sd_sid, org_name, is_sponsor, is_funder,
+        let sql = &schema_sql(r#"INSERT INTO sd.study_orgs (sd_sid, org_name, is_sponsor, is_funder,
                           is_collaborator, org_country, org_ror_id, org_cref_id)
             SELECT * FROM UNNEST($1::text[], $2::text[], $3::bool[], $4::bool[], $5::bool[],
-                     $6::text[], $7::text[], $8::text[])"#;
+                     $6::text[], $7::text[], $8::text[])"#);
 
         sqlx::query(sql)
         .bind(&self.sd_sids)
@@ -483,10 +484,10 @@ impl PeopleVecs{
 
     pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
 
-        let sql = r#"INSERT INTO sd.study_people (sd_sid, full_name, listed_as,
+        let sql = &schema_sql(r#"INSERT INTO sd.study_people (sd_sid, full_name, listed_as,
                         orcid_id, affiliation, email_domain)
             SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[],
-                        $4::text[], $5::text[], $6::text[])"#;
+                        $4::text[], $5::text[], $6::text[])"#);
 
         sqlx::query(sql)
         .bind(&self.sd_sids)
@@ -530,8 +531,8 @@ impl CountryVecs{
 
     pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
 
-        let sql = r#"INSERT INTO sd.study_countries (sd_sid, country_name)
-            SELECT * FROM UNNEST($1::text[], $2::text[])"#;
+        let sql = &schema_sql(r#"INSERT INTO sd.study_countries (sd_sid, country_name)
+            SELECT * FROM UNNEST($1::text[], $2::text[])"#);
 
         sqlx::query(sql)
         .bind(&self.sd_sids)
@@ -578,8 +579,8 @@ impl ConditionVecs {
 
     pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
 
-        let sql = r#"INSERT INTO sd.study_conditions (sd_sid, class1, class2, specific)
-            SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[])"#;
+        let sql = &schema_sql(r#"INSERT INTO sd.study_conditions (sd_sid, class1, class2, specific)
+            SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[])"#);
 
         sqlx::query(sql)
         .bind(&self.sd_sids)
@@ -628,8 +629,8 @@ impl FeatureVecs{
 
     pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
 
-        let sql = r#"INSERT INTO sd.study_features(sd_sid, source, feature_type, feature_value)
-            SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[])"#;
+        let sql = &schema_sql(r#"INSERT INTO sd.study_features(sd_sid, source, feature_type, feature_value)
+            SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[])"#);
 
         sqlx::query(sql)
         .bind(&self.sd_sids)
@@ -678,8 +679,8 @@ impl TopicVecs{
 
     pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
 
-        let sql = r#"INSERT INTO sd.study_topics (sd_sid, source, topic_type, topic_value)
-            SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[])"#;
+        let sql = &schema_sql(r#"INSERT INTO sd.study_topics (sd_sid, source, topic_type, topic_value)
+            SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[])"#);
 
         sqlx::query(sql)
         .bind(&self.sd_sids)
@@ -748,8 +749,8 @@ impl IECVecs{
 
     pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
 
-        let sql = r#"INSERT INTO sd.study_iec (sd_sid, seq_num, ie_type_id, split_type, tag, indent_level, indent_seq_num, sequence_string, criterion)
-            SELECT * FROM UNNEST($1::text[], $2::int[], $3::int[], $4::text[], $5::text[], $6::int[], $7::int[], $8::text[], $9::text[])"#;
+        let sql = &schema_sql(r#"INSERT INTO sd.study_iec (sd_sid, seq_num, ie_type_id, split_type, tag, indent_level, indent_seq_num, sequence_string, criterion)
+            SELECT * FROM UNNEST($1::text[], $2::int[], $3::int[], $4::text[], $5::text[], $6::int[], $7::int[], $8::text[], $9::text[])"#);
 
         sqlx::query(sql)
         .bind(&self.sd_sids)
@@ -767,6 +768,87 @@ impl IECVecs{
     }
 }
 
+pub struct IECConstraintVecs {
+    pub sd_sids: Vec<String>,
+    pub seq_nums:  Vec<i32>,
+    pub ie_type_ids:  Vec<i32>,
+    pub constraint_types: Vec<String>,
+    pub parameters: Vec<String>,
+    pub comparators: Vec<Option<String>>,
+    pub value_lows: Vec<Option<f64>>,
+    pub value_highs: Vec<Option<f64>>,
+    pub unitss: Vec<Option<String>>,
+    pub negateds: Vec<bool>,
+}
+
+impl IECConstraintVecs{
+    pub fn new(vsize: usize) -> Self {
+        IECConstraintVecs {
+            sd_sids: Vec::with_capacity(vsize),
+            seq_nums: Vec::with_capacity(vsize),
+            ie_type_ids: Vec::with_capacity(vsize),
+            constraint_types: Vec::with_capacity(vsize),
+            parameters: Vec::with_capacity(vsize),
+            comparators: Vec::with_capacity(vsize),
+            value_lows: Vec::with_capacity(vsize),
+            value_highs: Vec::with_capacity(vsize),
+            unitss: Vec::with_capacity(vsize),
+            negateds: Vec::with_capacity(vsize),
+        }
+    }
+
+    pub fn add(&mut self, sd_sid: &str, v: &[IECConstraint])
+    {
+        for r in v {
+            self.sd_sids.push(sd_sid.to_string());
+            self.seq_nums.push(r.seq_num);
+            self.ie_type_ids.push(r.ie_type_id);
+            self.constraint_types.push(r.constraint_type.clone());
+            self.parameters.push(r.parameter.clone());
+            self.comparators.push(r.comparator.clone());
+            self.value_lows.push(r.value_low);
+            self.value_highs.push(r.value_high);
+            self.unitss.push(r.units.clone());
+            self.negateds.push(r.negated);
+        }
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+
+            self.sd_sids.shrink_to_fit();
+            self.seq_nums.shrink_to_fit();
+            self.ie_type_ids.shrink_to_fit();
+            self.constraint_types.shrink_to_fit();
+            self.parameters.shrink_to_fit();
+            self.comparators.shrink_to_fit();
+            self.value_lows.shrink_to_fit();
+            self.value_highs.shrink_to_fit();
+            self.unitss.shrink_to_fit();
+            self.negateds.shrink_to_fit();
+    }
+
+    pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
+
+        let sql = &schema_sql(r#"INSERT INTO sd.study_iec_constraints (sd_sid, seq_num, ie_type_id, constraint_type, parameter, comparator, value_low, value_high, units, negated)
+            SELECT * FROM UNNEST($1::text[], $2::int[], $3::int[], $4::text[], $5::text[], $6::text[], $7::float[], $8::float[], $9::text[], $10::bool[])"#);
+
+        sqlx::query(sql)
+        .bind(&self.sd_sids)
+        .bind(&self.seq_nums)
+        .bind(&self.ie_type_ids)
+        .bind(&self.constraint_types)
+        .bind(&self.parameters)
+        .bind(&self.comparators)
+        .bind(&self.value_lows)
+        .bind(&self.value_highs)
+        .bind(&self.unitss)
+        .bind(&self.negateds)
+
+        .execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))
+    }
+}
+
 pub struct ObjectVecs {
     pub sd_sids: Vec<String>,
     pub object_types: Vec<String>,
@@ -845,11 +927,11 @@ impl ObjectVecs{
 
     pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
 
-        let sql = r#"INSERT INTO sd.study_objects (sd_sid, object_type, object_id, object_id_type, display_name,
+        let sql = &schema_sql(r#"INSERT INTO sd.study_objects (sd_sid, object_type, object_id, object_id_type, display_name,
                         date_created, date_published, date_updated, publication_year,
                         object_notes, access_url, access_type, url_target_type, instance_notes)
                 SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[], $5::text[], $6::date[],
-                         $7::date[], $8::date[], $9::int[], $10::text[], $11::text[], $12::text[], $13::text[], $14::text[])"#;
+                         $7::date[], $8::date[], $9::int[], $10::text[], $11::text[], $12::text[], $13::text[], $14::text[])"#);
 
         sqlx::query(sql)
         .bind(&self.sd_sids)
@@ -930,10 +1012,10 @@ impl PublicationVecs{
 
     pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
 
-        let sql = r#"INSERT INTO sd.study_pubs (sd_sid, pub_type, pub_id, pub_id_type, pub_notes,
+        let sql = &schema_sql(r#"INSERT INTO sd.study_pubs (sd_sid, pub_type, pub_id, pub_id_type, pub_notes,
                     date_created, date_published, date_updated, publication_year)
                     SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[], $5::text[],
-                         $6::date[], $7::date[], $8::date[], $9::int[])"#;
+                         $6::date[], $7::date[], $8::date[], $9::int[])"#);
         sqlx::query(sql)
         .bind(&self.sd_sids)
         .bind(&self.pub_types)
@@ -1007,10 +1089,10 @@ impl PubInstanceVecs{
 
     pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
 
-        let sql = r#"INSERT INTO sd.study_pub_instances(sd_sid, pub_id, instance_type, instance_id,
+        let sql = &schema_sql(r#"INSERT INTO sd.study_pub_instances(sd_sid, pub_id, instance_type, instance_id,
                     instance_lang, instance_notes, access_url, access_type, url_target_type)
                     SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[], $5::text[],
-                         $6::text[], $7::text[], $8::text[], $9::text[])"#;
+                         $6::text[], $7::text[], $8::text[], $9::text[])"#);
         sqlx::query(sql)
         .bind(&self.sd_sids)
         .bind(&self.pub_ids)
@@ -1061,13 +1143,13 @@ impl ImportUpdateVecs{
 
         // Row already exists - update with new details.
 
-        let sql = r#"Update mn.source_data s
+        let sql = &schema_sql(r#"Update mn.source_data s
                 set
                 last_import_id = u.imp_id,
                 last_imported = u.datetime_imported
                 from UNNEST($1::text[], $2::int[], $3::timestamptz[])
                      as u(sd_sid, imp_id, datetime_imported)
-                where s.sd_sid = u.sd_sid  "#;
+                where s.sd_sid = u.sd_sid  "#);
         sqlx::query(&sql).bind(&self.sd_sids).bind(&self.imp_ids).bind(&self.datetime_importeds)
         .execute(pool).await
         .map_err(|e| AppError::SqlxError(e, sql.to_string()))
@@ -1075,11 +1157,49 @@ impl ImportUpdateVecs{
 }
 
 
+pub struct ImportFailureVecs {
+    pub sd_sids: Vec<String>,
+    pub local_paths: Vec<String>,
+    pub errors: Vec<String>,
+    pub imp_ids: Vec<i32>,
+}
+
+
+impl ImportFailureVecs{
+    pub fn new(vsize: usize) -> Self {
+        ImportFailureVecs {
+            sd_sids: Vec::with_capacity(vsize),
+            local_paths: Vec::with_capacity(vsize),
+            errors: Vec::with_capacity(vsize),
+            imp_ids: Vec::with_capacity(vsize),
+        }
+    }
+
+    pub fn add(&mut self, sd_sid: &str, local_path: &str, error: &str, imp_id: i32)
+    {
+        self.sd_sids.push(sd_sid.to_string());
+        self.local_paths.push(local_path.to_string());
+        self.errors.push(error.to_string());
+        self.imp_ids.push(imp_id);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sd_sids.is_empty()
+    }
+
+    pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
+
+        let sql = &schema_sql(r#"INSERT INTO mn.import_failures (sd_sid, local_path, error, imp_event_id)
+                SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::int[])"#);
+        sqlx::query(sql).bind(&self.sd_sids).bind(&self.local_paths).bind(&self.errors).bind(&self.imp_ids)
+        .execute(pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))
+    }
+}
+
 
 
-// PREVIOUSLY INCLUDED - BEFORE DOWNLOAD WAS SIMPLIFIED
 
-/*
 pub struct LocationVecs {
     pub sd_sids: Vec<String>,
     pub fac_names: Vec<Option<String>>,
@@ -1124,8 +1244,8 @@ impl LocationVecs{
 
     pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
 
-        let sql = r#"INSERT INTO sd.study_locations (sd_sid, fac_name, fac_address, city_name, disamb_name, country_name)
-            SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[], $5::text[], $6::text[])"#;
+        let sql = &schema_sql(r#"INSERT INTO sd.study_locations (sd_sid, fac_name, fac_address, city_name, disamb_name, country_name)
+            SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[], $5::text[], $6::text[])"#);
 
         sqlx::query(sql)
         .bind(&self.sd_sids)
@@ -1138,4 +1258,124 @@ impl LocationVecs{
         .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))
     }
 }
-*/
+
+
+pub struct EthicsVecs {
+    pub sd_sids: Vec<String>,
+    pub approval_requireds: Vec<Option<String>>,
+    pub approval_notess: Vec<Option<String>>,
+    pub committee_names: Vec<Option<String>>,
+    pub approval_statuses: Vec<Option<String>>,
+    pub status_dates: Vec<Option<NaiveDate>>,
+    pub committee_refs: Vec<Option<String>>,
+}
+
+impl EthicsVecs{
+    pub fn new(vsize: usize) -> Self {
+        EthicsVecs {
+            sd_sids: Vec::with_capacity(vsize),
+            approval_requireds: Vec::with_capacity(vsize),
+            approval_notess: Vec::with_capacity(vsize),
+            committee_names: Vec::with_capacity(vsize),
+            approval_statuses: Vec::with_capacity(vsize),
+            status_dates: Vec::with_capacity(vsize),
+            committee_refs: Vec::with_capacity(vsize),
+        }
+    }
+
+    pub fn add(&mut self, sd_sid:&String, v: &Vec<DBEthics>)
+    {
+        for r in v {
+            self.sd_sids.push(sd_sid.clone());
+            self.approval_requireds.push(r.approval_required.clone());
+            self.approval_notess.push(r.approval_notes.clone());
+            self.committee_names.push(r.committee_name.clone());
+            self.approval_statuses.push(r.approval_status.clone());
+            self.status_dates.push(r.status_date);
+            self.committee_refs.push(r.committee_ref.clone());
+        }
+    }
+
+    pub fn shrink_to_fit(&mut self) -> () {
+        self.sd_sids.shrink_to_fit();
+        self.approval_requireds.shrink_to_fit();
+        self.approval_notess.shrink_to_fit();
+        self.committee_names.shrink_to_fit();
+        self.approval_statuses.shrink_to_fit();
+        self.status_dates.shrink_to_fit();
+        self.committee_refs.shrink_to_fit();
+    }
+
+    pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
+
+        let sql = &schema_sql(r#"INSERT INTO sd.study_ethics (sd_sid, approval_required, approval_notes,
+                        committee_name, approval_status, status_date, committee_ref)
+            SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[], $5::text[], $6::date[], $7::text[])"#);
+
+        sqlx::query(sql)
+        .bind(&self.sd_sids)
+        .bind(&self.approval_requireds)
+        .bind(&self.approval_notess)
+        .bind(&self.committee_names)
+        .bind(&self.approval_statuses)
+        .bind(&self.status_dates)
+        .bind(&self.committee_refs)
+        .execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))
+    }
+}
+
+
+pub struct OutcomeVecs {
+    pub sd_sids: Vec<String>,
+    pub outcome_types: Vec<String>,
+    pub variables: Vec<Option<String>>,
+    pub methods: Vec<Option<String>>,
+    pub timepointss: Vec<Option<String>>,
+}
+
+impl OutcomeVecs{
+    pub fn new(vsize: usize) -> Self {
+        OutcomeVecs {
+            sd_sids: Vec::with_capacity(vsize),
+            outcome_types: Vec::with_capacity(vsize),
+            variables: Vec::with_capacity(vsize),
+            methods: Vec::with_capacity(vsize),
+            timepointss: Vec::with_capacity(vsize),
+        }
+    }
+
+    pub fn add(&mut self, sd_sid:&String, v: &Vec<DBOutcome>)
+    {
+        for r in v {
+            self.sd_sids.push(sd_sid.clone());
+            self.outcome_types.push(r.outcome_type.clone());
+            self.variables.push(r.variable.clone());
+            self.methods.push(r.method.clone());
+            self.timepointss.push(r.timepoints.clone());
+        }
+    }
+
+    pub fn shrink_to_fit(&mut self) -> () {
+        self.sd_sids.shrink_to_fit();
+        self.outcome_types.shrink_to_fit();
+        self.variables.shrink_to_fit();
+        self.methods.shrink_to_fit();
+        self.timepointss.shrink_to_fit();
+    }
+
+    pub async fn store_data(&self, pool : &Pool<Postgres>) -> Result<PgQueryResult, AppError> {
+
+        let sql = &schema_sql(r#"INSERT INTO sd.study_outcomes (sd_sid, outcome_type, variable, method, timepoints)
+            SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[], $5::text[])"#);
+
+        sqlx::query(sql)
+        .bind(&self.sd_sids)
+        .bind(&self.outcome_types)
+        .bind(&self.variables)
+        .bind(&self.methods)
+        .bind(&self.timepointss)
+        .execute(pool)
+        .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))
+    }
+}
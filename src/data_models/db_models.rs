@@ -1,4 +1,4 @@
-use crate::iec::iec_structs::IECLine;
+use crate::iec::iec_structs::{IECLine, IECConstraint};
 use chrono::{NaiveDate, NaiveDateTime};
 
 
@@ -13,10 +13,14 @@ pub struct DBStudy {
     pub orgs: Option<Vec<DBOrganisation>>,
     pub people: Option<Vec<DBPerson>>,
     pub countries: Option<Vec<String>>,
+    pub locations: Option<Vec<DBLocation>>,
+    pub ethics: Option<Vec<DBEthics>>,
+    pub outcomes: Option<Vec<DBOutcome>>,
     pub conditions: Option<Vec<DBCondition>>,
     pub features: Option<Vec<DBFeature>>,
     pub topics: Option<Vec<DBTopic>>,
     pub ie_crit: Option<Vec<IECLine>>,
+    pub iec_constraints: Option<Vec<IECConstraint>>,
     pub objects: Option<Vec<DBObject>>,
     pub publications: Option<Vec<DBPublication>>,
     pub pub_instances: Option<Vec<DBPublicationInstance>>,
@@ -165,9 +169,6 @@ pub struct DBPublicationInstance {
 
 
 
-// PREVIOUSLY INCLUDED - BEFORE DOWNLOAD WAS SIMPLIFIED
-
-/*
 pub struct DBLocation {
     pub fac_name: Option<String>,
     pub fac_address: Option<String>,
@@ -175,4 +176,19 @@ pub struct DBLocation {
     pub disamb_name: Option<String>,
     pub country_name: Option<String>,
 }
-*/
+
+pub struct DBOutcome {
+    pub outcome_type: String,
+    pub variable: Option<String>,
+    pub method: Option<String>,
+    pub timepoints: Option<String>,
+}
+
+pub struct DBEthics {
+    pub approval_required: Option<String>,
+    pub approval_notes: Option<String>,
+    pub committee_name: Option<String>,
+    pub approval_status: Option<String>,
+    pub status_date: Option<NaiveDate>,
+    pub committee_ref: Option<String>,
+}
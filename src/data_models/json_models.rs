@@ -1,9 +1,17 @@
 use serde::{Serialize, Deserialize};
 
+// The version of the json file structure, written into each file. It must be incremented
+// whenever the Study model changes, with a corresponding change to the json schema
+// (in json_schema.rs) and a migration step for older files (in import/migration.rs).
+// Version 1 files, written before versioning was introduced, have no schema_version field.
+
+pub const SCHEMA_VERSION: u32 = 2;
+
 #[derive(Serialize, Deserialize)]
 pub struct Study
 {
     pub sd_sid: String, 
+    pub schema_version: u32,
     pub downloaded: String,
 
     pub public_title: Option<String>,
@@ -15,6 +23,7 @@ pub struct Study
     
     pub summary: Summary,
     pub primary_outcomes: Option<Vec<OutcomeMeasure>>,
+    pub secondary_outcomes: Option<Vec<OutcomeMeasure>>,
 
     pub design: Design,
     pub trial_types: Option<Vec<String>>,
@@ -31,6 +40,10 @@ pub struct Study
 
     pub recruitment: Recruitment,
     pub countries: Option<Vec<String>>,
+    pub centres: Option<Vec<StudyCentre>>,
+
+    pub ethics: Option<Ethics>,
+    pub ethics_committees: Option<Vec<EthicsCommittee>>,
 
     pub data_policies: Option<Vec<String>>,
     pub results: Results,
@@ -78,6 +91,7 @@ pub struct Summary
     pub plain_english_summary: Option<String>,
     pub study_hypothesis: Option<String>,
     pub primary_outcome: Option<String>,
+    pub secondary_outcome: Option<String>,
     pub overall_end_date: Option<String>,
     pub trial_website: Option<String>,
 }
@@ -230,28 +244,6 @@ pub struct LocalFile
 }
 
 
-#[derive(Serialize, Deserialize)]
-pub struct StudyFile
-{
-    pub file_type: Option<String>,
-    pub file_id: Option<String>,
-    pub description: Option<String>,
-    pub download_url: Option<String>,       
-    pub download_filename: Option<String>,
-    pub name: Option<String>,
-    pub version: Option<String>,
-    pub length: Option<i32>,
-    pub mime_type: Option<String>,
-    pub is_public: Option<bool>, 
-    pub date_created: Option<String>,
-    pub date_uploaded: Option<String>,
-}
-
-
-
-// PREVIOUSLY INCLUDED - BEFORE DOWNLOAD WAS SIMPLIFIED
-
-/*
 #[derive(Serialize, Deserialize)]
 pub struct StudyCentre
 {
@@ -261,9 +253,8 @@ pub struct StudyCentre
     pub state: Option<String>,
     pub country: Option<String>,
 }
-*/
 
-/* 
+
 #[derive(Serialize, Deserialize)]
 pub struct Ethics
 {
@@ -271,6 +262,7 @@ pub struct Ethics
     pub ethics_approval: Option<String>,
 }
 
+
 #[derive(Serialize, Deserialize)]
 pub struct EthicsCommittee
 {
@@ -279,4 +271,23 @@ pub struct EthicsCommittee
     pub status_date: Option<String>,
     pub committee_reference: Option<String>,
 }
-*/
+
+
+#[derive(Serialize, Deserialize)]
+pub struct StudyFile
+{
+    pub file_type: Option<String>,
+    pub file_id: Option<String>,
+    pub description: Option<String>,
+    pub download_url: Option<String>,       
+    pub download_filename: Option<String>,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub length: Option<i32>,
+    pub mime_type: Option<String>,
+    pub is_public: Option<bool>, 
+    pub date_created: Option<String>,
+    pub date_uploaded: Option<String>,
+}
+
+
@@ -0,0 +1,322 @@
+use super::json_models::SCHEMA_VERSION;
+use crate::AppError;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// The JSON Schema (draft 2020-12) describing the study json files, i.e. the json_models::Study
+// struct as serialised by serde. Optional fields are nullable, but all fields are always
+// written, so all are listed as required. The test below checks that the schema and the
+// model match exactly, so that the schema cannot be left behind when the model changes.
+// The schema is written out here rather than derived (e.g. with schemars) because a derived
+// schema treats Option fields as ones that may be left out, rather than as required but
+// nullable, and would add a dependency, and derive attributes on every model struct, to
+// produce a single file. Each nested object is built separately, as the json! macro
+// cannot expand the whole schema within the compiler's default recursion limit.
+
+pub const SCHEMA_FILE_NAME: &str = "study.schema.json";
+
+pub fn study_schema() -> Value {
+
+    let outcome_measure = object(json!({
+        "variable": nullable("string"),
+        "method": nullable("string"),
+        "timepoints": nullable("string"),
+    }));
+
+    let registration = object(json!({
+        "date_id_assigned": nullable("string"),
+        "last_updated": nullable("string"),
+        "version": nullable("string"),
+        "doi": nullable("string"),
+    }));
+
+    let identifier = object(json!({
+        "identifier_type_id": { "type": "integer" },
+        "identifier_type": { "type": "string" },
+        "identifier_value": { "type": "string" },
+    }));
+
+    let summary = object(json!({
+        "plain_english_summary": nullable("string"),
+        "study_hypothesis": nullable("string"),
+        "primary_outcome": nullable("string"),
+        "secondary_outcome": nullable("string"),
+        "overall_end_date": nullable("string"),
+        "trial_website": nullable("string"),
+    }));
+
+    let design = object(json!({
+        "study_design": nullable("string"),
+        "primary_study_design": nullable("string"),
+        "secondary_study_design": nullable("string"),
+    }));
+
+    let condition = object(json!({
+        "description": nullable("string"),
+        "disease_class1": nullable("string"),
+        "disease_class2": nullable("string"),
+    }));
+
+    let intervention = object(json!({
+        "description": nullable("string"),
+        "int_type": nullable("string"),
+        "pharma_study_types": nullable("string"),
+        "phase": nullable("string"),
+        "drug_names": nullable("string"),
+    }));
+
+    let contact = object(json!({
+        "title": nullable("string"),
+        "forename": nullable("string"),
+        "surname": nullable("string"),
+        "orcid": nullable("string"),
+        "contact_types": nullable_array(json!({ "type": "string" })),
+        "address": nullable("string"),
+        "city": nullable("string"),
+        "country": nullable("string"),
+        "email": nullable("string"),
+        "privacy": nullable("string"),
+    }));
+
+    let sponsor = object(json!({
+        "organisation": nullable("string"),
+        "website": nullable("string"),
+        "sponsor_type": nullable("string"),
+        "ror_id": nullable("string"),
+        "address": nullable("string"),
+        "city": nullable("string"),
+        "country": nullable("string"),
+        "email": nullable("string"),
+        "privacy": nullable("string"),
+        "commercial_status": nullable("string"),
+    }));
+
+    let funder = object(json!({
+        "name": nullable("string"),
+        "fund_ref": nullable("string"),
+    }));
+
+    let participants = object(json!({
+        "age_range": nullable("string"),
+        "l_age_limit": nullable("string"),
+        "l_age_limit_num": nullable("number"),
+        "l_age_limit_units": nullable("string"),
+        "u_age_limit": nullable("string"),
+        "u_age_limit_num": nullable("number"),
+        "u_age_limit_units": nullable("string"),
+        "gender": nullable("string"),
+        "inclusion": nullable("string"),
+        "exclusion": nullable("string"),
+        "patient_info_sheet": nullable("string"),
+    }));
+
+    let recruitment = object(json!({
+        "target_enrolment": nullable("string"),
+        "total_final_enrolment": nullable("string"),
+        "total_target": nullable("string"),
+        "recruitment_start": nullable("string"),
+        "recruitment_end": nullable("string"),
+        "recruitment_start_status_override": nullable("string"),
+        "recruitment_status_override": nullable("string"),
+    }));
+
+    let centre = object(json!({
+        "name": nullable("string"),
+        "address": nullable("string"),
+        "city": nullable("string"),
+        "state": nullable("string"),
+        "country": nullable("string"),
+    }));
+
+    let ethics = nullable_object(json!({
+        "ethics_approval_required": nullable("string"),
+        "ethics_approval": nullable("string"),
+    }));
+
+    let ethics_committee = object(json!({
+        "name": nullable("string"),
+        "approval_status": nullable("string"),
+        "status_date": nullable("string"),
+        "committee_reference": nullable("string"),
+    }));
+
+    let results = object(json!({
+        "publication_plan": nullable("string"),
+        "intent_to_publish": nullable("string"),
+        "publication_details": nullable("string"),
+        "publication_stage": nullable("string"),
+        "biomed_related": nullable("boolean"),
+        "basic_report": nullable("string"),
+        "plain_english_report": nullable("string"),
+    }));
+
+    let link = object(json!({
+        "link_type": nullable("string"),
+        "link_url": nullable("string"),
+        "description": nullable("string"),
+        "date_created": nullable("string"),
+        "date_uploaded": nullable("string"),
+    }));
+
+    let file = object(json!({
+        "file_type": nullable("string"),
+        "file_id": nullable("string"),
+        "description": nullable("string"),
+        "download_url": nullable("string"),
+        "download_filename": nullable("string"),
+        "name": nullable("string"),
+        "version": nullable("string"),
+        "length": nullable("integer"),
+        "mime_type": nullable("string"),
+        "is_public": nullable("boolean"),
+        "date_created": nullable("string"),
+        "date_uploaded": nullable("string"),
+    }));
+
+    let ipd = object(json!({
+        "ipd_sharing_plan": { "type": "boolean" },
+        "ipd_sharing_statement": nullable("string"),
+    }));
+
+    let mut schema = object(json!({
+        "sd_sid": { "type": "string" },
+        "schema_version": { "type": "integer", "const": SCHEMA_VERSION },
+        "downloaded": { "type": "string" },
+        "public_title": nullable("string"),
+        "scientific_title": nullable("string"),
+        "acronym": nullable("string"),
+        "registration": registration,
+        "identifiers": nullable_array(identifier),
+        "summary": summary,
+        "primary_outcomes": nullable_array(outcome_measure.clone()),
+        "secondary_outcomes": nullable_array(outcome_measure),
+        "design": design,
+        "trial_types": nullable_array(json!({ "type": "string" })),
+        "conditions": nullable_array(condition),
+        "interventions": nullable_array(intervention),
+        "contacts": nullable_array(contact),
+        "sponsors": nullable_array(sponsor),
+        "funders": nullable_array(funder),
+        "participant_types": nullable_array(json!({ "type": "string" })),
+        "participants": participants,
+        "recruitment": recruitment,
+        "countries": nullable_array(json!({ "type": "string" })),
+        "centres": nullable_array(centre),
+        "ethics": ethics,
+        "ethics_committees": nullable_array(ethics_committee),
+        "data_policies": nullable_array(json!({ "type": "string" })),
+        "results": results,
+        "links": nullable_array(link),
+        "files": nullable_array(file),
+        "ipd": ipd,
+    }));
+
+    let header = json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": format!("isrctn-study-v{}", SCHEMA_VERSION),
+        "title": "ISRCTN study",
+        "description": "A single ISRCTN study, as downloaded and processed from the ISRCTN API",
+    });
+    if let (Some(s), Some(h)) = (schema.as_object_mut(), header.as_object()) {
+        for (k, v) in h {
+            s.insert(k.clone(), v.clone());
+        }
+    }
+    schema
+}
+
+
+pub fn write_schema_file(json_folder: &Path) -> Result<PathBuf, AppError> {
+
+    // Written to the top of the json folder at the start of each download,
+    // so that the files are always accompanied by a description of their structure.
+
+    let file_path = json_folder.join(SCHEMA_FILE_NAME);
+    let schema_string = serde_json::to_string_pretty(&study_schema())?;
+    fs::write(&file_path, schema_string)
+        .map_err(|e| AppError::IoWriteErrorWithPath(e, file_path.clone()))?;
+    Ok(file_path)
+}
+
+
+fn nullable(type_name: &str) -> Value {
+    json!({ "type": [type_name, "null"] })
+}
+
+fn nullable_array(items: Value) -> Value {
+    json!({ "type": ["array", "null"], "items": items })
+}
+
+fn object(properties: Value) -> Value {
+    typed_object(json!("object"), properties)
+}
+
+fn nullable_object(properties: Value) -> Value {
+    typed_object(json!(["object", "null"]), properties)
+}
+
+fn typed_object(type_value: Value, properties: Value) -> Value {
+    let required: Vec<String> = match properties.as_object() {
+        Some(p) => p.keys().cloned().collect(),
+        None => Vec::new(),
+    };
+    json!({
+        "type": type_value,
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_models::json_models::Study;
+
+    // Builds an instance of the schema with every value present (i.e. not null),
+    // and every array holding one item, so that every field is exercised.
+
+    fn sample_from_schema(schema: &Value) -> Value {
+        let type_name = match &schema["type"] {
+            Value::String(s) => s.as_str(),
+            Value::Array(a) => a[0].as_str().unwrap(),
+            _ => panic!("schema without a type: {}", schema),
+        };
+        if let Some(c) = schema.get("const") {
+            return c.clone();
+        }
+        match type_name {
+            "object" => {
+                let mut obj = serde_json::Map::new();
+                for (k, v) in schema["properties"].as_object().unwrap() {
+                    obj.insert(k.clone(), sample_from_schema(v));
+                }
+                Value::Object(obj)
+            },
+            "array" => json!([sample_from_schema(&schema["items"])]),
+            "string" => json!("text"),
+            "integer" => json!(7),
+            "number" => json!(1.5),
+            "boolean" => json!(true),
+            _ => panic!("unexpected type {}", type_name),
+        }
+    }
+
+    #[test]
+    fn check_schema_matches_study_model() {
+        let sample = sample_from_schema(&study_schema());
+        let study: Study = serde_json::from_value(sample.clone()).unwrap();
+        let round_trip = serde_json::to_value(&study).unwrap();
+        assert_eq!(sample, round_trip);
+    }
+
+    #[test]
+    fn check_schema_carries_current_version() {
+        let schema = study_schema();
+        assert_eq!(schema["$id"], json!(format!("isrctn-study-v{}", SCHEMA_VERSION)));
+        assert_eq!(schema["properties"]["schema_version"]["const"], json!(SCHEMA_VERSION));
+        assert!(schema["required"].as_array().unwrap().contains(&json!("sd_sid")));
+    }
+}
@@ -1,4 +1,6 @@
 pub mod json_models;
+pub mod json_schema;
+pub mod study_diff;
 pub mod xml_models;
 pub mod db_models;
 pub mod data_vecs;
\ No newline at end of file
@@ -0,0 +1,158 @@
+use super::json_models::Study;
+use crate::AppError;
+use serde_json::Value;
+
+// Compares two versions of a study, as held in the json files, field by field. The studies
+// are compared as json values, so that every field in the model is covered without needing
+// to be listed here. Objects are compared field by field, but lists (e.g. of outcome
+// measures or identifiers) are compared as a whole, and reported as a single change.
+// Field paths use the json names, with nested fields joined by '.', e.g.
+// 'recruitment.target_enrolment', 'summary.overall_end_date', 'primary_outcomes'.
+
+#[derive(Debug, PartialEq)]
+pub struct FieldChange {
+    pub field_path: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+// Fields that differ between any two downloads, or between model versions, rather than
+// because the registry record has changed.
+
+const IGNORED_FIELDS: [&str; 2] = ["downloaded", "schema_version"];
+
+
+pub fn diff_studies(old: &Study, new: &Study) -> Result<Vec<FieldChange>, AppError> {
+
+    let mut changes = Vec::new();
+    diff_values("", &serde_json::to_value(old)?, &serde_json::to_value(new)?, &mut changes);
+    Ok(changes)
+}
+
+
+fn diff_values(path: &str, old: &Value, new: &Value, changes: &mut Vec<FieldChange>) {
+
+    match (old, new) {
+        (Value::Object(o), Value::Object(n)) => {
+
+            // Both maps are ordered by key, so the fields are listed in a stable order.
+
+            let mut keys: Vec<&String> = o.keys().chain(n.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for k in keys {
+                if path.is_empty() && IGNORED_FIELDS.contains(&k.as_str()) {
+                    continue;
+                }
+                let child_path = if path.is_empty() {k.to_string()} else {format!("{}.{}", path, k)};
+                diff_values(&child_path, o.get(k).unwrap_or(&Value::Null),
+                            n.get(k).unwrap_or(&Value::Null), changes);
+            }
+        },
+        _ => {
+            if old != new {
+                changes.push(FieldChange {
+                    field_path: path.to_string(),
+                    old_value: value_as_string(old),
+                    new_value: value_as_string(new),
+                });
+            }
+        },
+    }
+}
+
+
+fn value_as_string(v: &Value) -> Option<String> {
+    match v {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        _ => Some(v.to_string()),     // numbers, booleans, lists and objects as json
+    }
+}
+
+
+pub fn format_change_report(dl_id: i32, changes: &[(String, FieldChange)]) -> String {
+
+    // Changes are expected to be ordered by study, and listed under each study's id.
+
+    let mut num_studies = 0;
+    let mut lines = Vec::new();
+    let mut current_sid = "";
+    for (sd_sid, c) in changes {
+        if sd_sid != current_sid {
+            lines.push(sd_sid.to_string());
+            current_sid = sd_sid;
+            num_studies += 1;
+        }
+        lines.push(format!("    {}: {} -> {}", c.field_path,
+                    c.old_value.as_deref().unwrap_or("(none)"), c.new_value.as_deref().unwrap_or("(none)")));
+    }
+    let header = format!("Download {}: {} field changes in {} studies", dl_id, changes.len(), num_studies);
+    std::iter::once(header).chain(lines).collect::<Vec<String>>().join("\n")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn diff_json(old: Value, new: Value) -> Vec<FieldChange> {
+        let mut changes = Vec::new();
+        diff_values("", &old, &new, &mut changes);
+        changes
+    }
+
+    #[test]
+    fn check_nested_field_changes_are_listed() {
+        let old = json!({"sd_sid": "ISRCTN14757012", "downloaded": "2025-10-18T06:00:00",
+                         "recruitment": {"target_enrolment": "120", "recruitment_status_override": "Recruiting"},
+                         "summary": {"overall_end_date": "2026-01-31"}});
+        let new = json!({"sd_sid": "ISRCTN14757012", "downloaded": "2026-02-01T06:00:00",
+                         "recruitment": {"target_enrolment": "150", "recruitment_status_override": "Completed"},
+                         "summary": {"overall_end_date": "2026-01-31"}});
+        let changes = diff_json(old, new);
+        assert_eq!(changes, vec![
+            FieldChange { field_path: "recruitment.recruitment_status_override".to_string(),
+                          old_value: Some("Recruiting".to_string()), new_value: Some("Completed".to_string()) },
+            FieldChange { field_path: "recruitment.target_enrolment".to_string(),
+                          old_value: Some("120".to_string()), new_value: Some("150".to_string()) },
+        ]);
+    }
+
+    #[test]
+    fn check_lists_compared_as_a_whole() {
+        let old = json!({"primary_outcomes": [{"variable": "Stye recurrence", "timepoints": "6 months"}]});
+        let new = json!({"primary_outcomes": [{"variable": "Stye recurrence", "timepoints": "12 months"}]});
+        let changes = diff_json(old, new);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].field_path, "primary_outcomes");
+        assert_eq!(changes[0].new_value, Some(r#"[{"timepoints":"12 months","variable":"Stye recurrence"}]"#.to_string()));
+    }
+
+    #[test]
+    fn check_added_and_removed_values_are_listed() {
+        let old = json!({"acronym": null, "ethics": {"ethics_approval": "Approved"}});
+        let new = json!({"acronym": "STYE", "ethics": null});
+        let changes = diff_json(old, new);
+        assert_eq!(changes[0], FieldChange { field_path: "acronym".to_string(), old_value: None, new_value: Some("STYE".to_string()) });
+        assert_eq!(changes[1].field_path, "ethics");
+        assert_eq!(changes[1].new_value, None);
+    }
+
+    #[test]
+    fn check_change_report_groups_by_study() {
+        let fc = |p: &str, o: &str, n: &str| FieldChange { field_path: p.to_string(), old_value: Some(o.to_string()), new_value: Some(n.to_string()) };
+        let changes = vec![
+            ("ISRCTN1".to_string(), fc("recruitment.recruitment_status_override", "Recruiting", "Completed")),
+            ("ISRCTN1".to_string(), fc("summary.overall_end_date", "2025-12-31", "2026-06-30")),
+            ("ISRCTN2".to_string(), fc("recruitment.target_enrolment", "100", "80")),
+        ];
+        let report = format_change_report(10234, &changes);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "Download 10234: 3 field changes in 2 studies");
+        assert_eq!(lines[1], "ISRCTN1");
+        assert_eq!(lines[2], "    recruitment.recruitment_status_override: Recruiting -> Completed");
+        assert_eq!(lines[4], "ISRCTN2");
+    }
+}
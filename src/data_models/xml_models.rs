@@ -11,6 +11,33 @@ pub struct TrialsCount
 }
 
 
+#[derive(serde::Deserialize, Debug, PartialEq)]
+#[serde(rename = "allTrials")]
+pub struct AllTrialIds
+{
+    // just used to list the ISRCTN ids of the trials returned by an API call
+    // The rest of the data is ignored
+
+    #[serde(rename = "@totalCount")]
+    pub total_count: i32,
+
+    #[serde(rename = "fullTrial", default)]
+    pub full_trials: Vec<TrialIdOnly>,
+}
+
+#[derive(serde::Deserialize, Debug, PartialEq)]
+pub struct TrialIdOnly
+{
+    pub trial: TrialIsrctn,
+}
+
+#[derive(serde::Deserialize, Debug, PartialEq)]
+pub struct TrialIsrctn
+{
+    pub isrctn: Isrctn,
+}
+
+
 #[derive(serde::Deserialize, Debug, PartialEq)]
 #[serde(rename = "allTrials")]
 pub struct AllTrials
@@ -104,9 +131,19 @@ pub struct Description
     pub primary_outcomes: PrimOutcomesList,
     #[serde(rename = "primaryOutcome", default)]
     pub primary_outcome: Option<String>,
+    #[serde(rename = "secondaryOutcomes")]
+    pub secondary_outcomes: Option<SecOutcomesList>,
+    #[serde(rename = "secondaryOutcome", default)]
+    pub secondary_outcome: Option<String>,
     #[serde(rename = "trialWebsite")]
     pub trial_website: Option<String>,
 
+    #[serde(rename = "ethicsApprovalRequired")]
+    pub ethics_approval_required: Option<String>,
+    #[serde(rename = "ethicsCommittees")]
+    pub ethics_committee_list: Option<EthicsCommitteeList>,
+    #[serde(rename = "ethicsApproval")]
+    pub ethics_approval: Option<String>,
 }
 
 
@@ -118,6 +155,14 @@ pub struct PrimOutcomesList
 }
 
 
+#[derive(serde::Deserialize, Debug, PartialEq)]
+pub struct SecOutcomesList
+{
+    #[serde(rename = "outcomeMeasure", default)]
+    pub outcome_measures: Vec<OutcomeMeasure>,
+}
+
+
 #[derive(serde::Deserialize, Debug, PartialEq)]
 pub struct OutcomeMeasure
 {
@@ -127,6 +172,32 @@ pub struct OutcomeMeasure
 }
 
 
+#[derive(serde::Deserialize, Debug, PartialEq)]
+pub struct EthicsCommitteeList
+{
+    #[serde(rename = "ethicsCommittee", default)]
+    pub ethics_committees: Vec<EthicsCommittee>,
+}
+
+
+#[derive(serde::Deserialize, Debug, PartialEq)]
+pub struct EthicsCommittee
+{
+    #[serde(rename = "@id")]
+    pub id: Option<String>,
+    #[serde(rename = "@approvalStatus")]
+    pub approval_status: Option<String>,
+    #[serde(rename = "@statusDate")]
+    pub status_date: Option<String>,
+    #[serde(rename = "committeeName")]
+    pub committee_name: Option<String>,
+    #[serde(rename = "contactDetails")]
+    pub contact_details: Option<ContactDetails>,
+    #[serde(rename = "committeeReference")]
+    pub committee_reference: Option<String>,
+}
+
+
 #[derive(serde::Deserialize, Debug, PartialEq)]
 pub struct ExternalRefs
 {
@@ -197,6 +268,9 @@ pub struct Participants
 {
     #[serde(rename = "recruitmentCountries")]
     pub country_list: CountryList,
+
+    #[serde(rename = "trialCentres")]
+    pub centre_list: Option<CentreList>,
   
     #[serde(rename = "participantTypes")]
     pub participant_type_list: ParticipantTypeList,
@@ -244,6 +318,26 @@ pub struct Country
     pub country: Option<String>,
 }
 
+#[derive(serde::Deserialize, Debug, PartialEq)]
+pub struct CentreList
+{
+    #[serde(rename = "trialCentre", default)]
+    pub centres: Vec<Centre>,
+}
+
+#[derive(serde::Deserialize, Debug, PartialEq)]
+pub struct Centre
+{
+    #[serde(rename = "@id")]
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub address: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
+    pub zip: Option<String>,
+}
+
 #[derive(serde::Deserialize, Debug, PartialEq)]
 pub struct ParticipantTypeList
 {
@@ -532,78 +626,7 @@ pub struct Funder
 
 // PREVIOUSLY INCLUDED - BEFORE DOWNLOAD WAS SIMPLIFIED
 
-//    #[serde(rename = "trialCentres")]
-//    pub centre_list: CentreList,
-
-/*
-#[derive(serde::Deserialize, Debug, PartialEq)]
-pub struct SecOutcomesList
-{
-    #[serde(rename = "outcomeMeasure", default)]
-    pub outcome_measures: Vec<OutcomeMeasure>,
-}
-*/
-  //  #[serde(rename = "secondaryOutcomes")]
-  // pub secondary_outcomes: SecOutcomesList,
-  //  #[serde(rename = "secondaryOutcome")]
-  //  pub secondary_outcome: Option<String>,
-
-  //  #[serde(rename = "ethicsApprovalRequired")]
-  //   pub ethics_approval_required: Option<String>,
-  //   #[serde(rename = "ethicsCommittees")]
-  //   pub ethics_committee_list: EthicsCommitteeList,
-  //   #[serde(rename = "ethicsApproval")]
-  //  pub ethics_approval: Option<String>,
-
-/* 
-#[derive(serde::Deserialize, Debug, PartialEq)]
-pub struct EthicsCommitteeList
-{
-    #[serde(rename = "ethicsCommittee", default)]
-    pub ethics_committees: Vec<EthicsCommittee>,
-}
-
-#[derive(serde::Deserialize, Debug, PartialEq)]
-pub struct EthicsCommittee
-{
-    #[serde(rename = "@id")]
-    pub id: Option<String>,
-    #[serde(rename = "@approvalStatus")]
-    pub approval_status: Option<String>,
-    #[serde(rename = "@statusDate")]
-    pub status_date: Option<String>,
-    #[serde(rename = "committeeName")]
-    pub committee_name: Option<String>,
-    #[serde(rename = "contactDetails")]
-    pub contact_details: Option<ContactDetails>,
-    #[serde(rename = "committeeReference")]
-    pub committee_reference: Option<String>,
-}
-*/
-
-
-/* 
-#[derive(serde::Deserialize, Debug, PartialEq)]
-pub struct CentreList
-{
-    #[serde(rename = "trialCentre", default)]
-    pub centres: Vec<Centre>,
-}
-
-#[derive(serde::Deserialize, Debug, PartialEq)]
-pub struct Centre
-{
-    #[serde(rename = "@id")]
-    pub id: Option<String>,
-    pub name: Option<String>,
-    pub address: Option<String>,
-    pub city: Option<String>,
-    pub state: Option<String>,
-    pub country: Option<String>,
-    pub zip: Option<String>,
 
-}
-*/
 
 /* 
 
@@ -644,6 +667,28 @@ mod tests {
         assert_eq!(isrctn, der_struct);
     }  
 
+    #[test]
+    fn check_can_parse_trial_ids_only() {
+
+        let xml_string = r#"<allTrials totalCount="2">
+            <fullTrial>
+                <trial lastUpdated="2025-03-01T10:00:00Z" version="3">
+                    <isrctn dateAssigned="2025-02-26T07:23:16.665489Z">10601218</isrctn>
+                    <trialDescription><title>A trial</title></trialDescription>
+                </trial>
+                <contact id="c1"><forename>Ann</forename></contact>
+            </fullTrial>
+            <fullTrial>
+                <trial><isrctn>14757012</isrctn></trial>
+            </fullTrial>
+        </allTrials>"#;
+
+        let der_struct: AllTrialIds = quick_xml::de::from_str(xml_string).unwrap();
+        assert_eq!(der_struct.total_count, 2);
+        let ids: Vec<&str> = der_struct.full_trials.iter().map(|t| t.trial.isrctn.value.as_str()).collect();
+        assert_eq!(ids, vec!["10601218", "14757012"]);
+    }
+
      #[test]
     fn check_can_parse_secondary_numbers() {
 
@@ -795,7 +840,24 @@ mod tests {
         let po_list = PrimOutcomesList {
             outcome_measures: vec![],
         };
-       
+
+        let ec = EthicsCommittee {
+            id: Some("5247b77a-f096-40ed-9b87-e836ee9d7a68".to_string()),
+            approval_status: Some("approved".to_string()),
+            status_date: Some("2022-03-10T00:00:00.000Z".to_string()),
+            committee_name: Some("Haikou Municipal People's Hospital Biomedical Ethics Committee".to_string()),
+            contact_details: Some(ContactDetails {
+                address: Some("No.43 Renmin Avenue".to_string()),
+                city: Some("Haikou".to_string()),
+                state: Some("".to_string()),
+                country: Some("China".to_string()),
+                zip: Some("570208".to_string()),
+                telephone: Some("".to_string()),
+                email: Some("".to_string()),
+            }),
+            committee_reference: Some("2023-055".to_string()),
+        };
+
         let td = Description {
             third_party_ack: Some("true".to_string()),
             acknowledgment: Some("true".to_string()),
@@ -806,7 +868,12 @@ mod tests {
             plain_english_summary: Some("Background and study aims In this study, patients with bladder cancer were examined".to_string()),
             primary_outcomes: po_list,
             primary_outcome: Some("Survival measured using data collected at an annual telephone call for 6 years".to_string()),
+            secondary_outcomes: Some(SecOutcomesList { outcome_measures: vec![] }),
+            secondary_outcome: Some("Transfer or not measured using data collected during a telephone call every 6 months".to_string()),
             trial_website: Some("".to_string()),
+            ethics_approval_required: Some("Ethics approval required".to_string()),
+            ethics_committee_list: Some(EthicsCommitteeList { ethics_committees: vec![ec] }),
+            ethics_approval: None,
         };
 
         let der_struct: Description = quick_xml::de::from_str(xml_string).unwrap();
@@ -895,7 +962,40 @@ mod tests {
         let po_list  = PrimOutcomesList { 
             outcome_measures: vec![pom1, pom2, pom3],
         };
-         
+
+        let som1 = OutcomeMeasure {
+            variable: Some("Depression and anxiety symptoms".to_string()),
+            method: Some("Revised Child Anxiety and Depression Scale, 25-item short version (RCADS-25)".to_string()),
+            timepoints: Some("Baseline (T0), 3 months (T1), 6 months (T2)".to_string()),
+        };
+
+        let som2 = OutcomeMeasure {
+            variable: Some("Suicide literacy (knowledge about suicide)".to_string()),
+            method: Some("Literacy of Suicide Scale short form (LOSS)".to_string()),
+            timepoints: Some("Baseline (T0), 3 months (T1), 6 months (T2)".to_string()),
+        };
+
+        let so_list  = SecOutcomesList {
+            outcome_measures: vec![som1, som2],
+        };
+
+        let ec = EthicsCommittee {
+            id: Some("5247b77a-f096-40ed-9b87-e836ee9d7a68".to_string()),
+            approval_status: Some("approved".to_string()),
+            status_date: Some("2022-03-10T00:00:00.000Z".to_string()),
+            committee_name: Some("Haikou Municipal People's Hospital Biomedical Ethics Committee".to_string()),
+            contact_details: Some(ContactDetails {
+                address: Some("No.43 Renmin Avenue".to_string()),
+                city: Some("Haikou".to_string()),
+                state: Some("".to_string()),
+                country: Some("China".to_string()),
+                zip: Some("570208".to_string()),
+                telephone: Some("".to_string()),
+                email: Some("".to_string()),
+            }),
+            committee_reference: Some("2023-055".to_string()),
+        };
+
         let td = Description {
             third_party_ack: Some("true".to_string()),
             acknowledgment: Some("true".to_string()),
@@ -906,7 +1006,12 @@ mod tests {
             plain_english_summary: Some("Background and study aims In this study, patients with bladder cancer were examined".to_string()),
             primary_outcomes: po_list,
             primary_outcome: Some("".to_string()),
+            secondary_outcomes: Some(so_list),
+            secondary_outcome: Some("".to_string()),
             trial_website: Some("".to_string()),
+            ethics_approval_required: Some("Ethics approval required".to_string()),
+            ethics_committee_list: Some(EthicsCommitteeList { ethics_committees: vec![ec] }),
+            ethics_approval: None,
         };
 
         let der_struct: Description = quick_xml::de::from_str(xml_string).unwrap();
@@ -1038,8 +1143,33 @@ mod tests {
             value: Some("80 Years".to_string()),
         };
    
+        let ct1 = Centre {
+            id: Some("d1b6a41f-06b2-47d3-a920-9758f4dadbcc".to_string()),
+            name: Some("Complife Italia srl".to_string()),
+            address: Some("Corso San Maurizio, 25A ".to_string()),
+            city: Some("Biella (BI)".to_string()),
+            state: Some("".to_string()),
+            country: Some("Italy".to_string()),
+            zip: Some("13900".to_string()),
+        };
+
+        let ct2 = Centre {
+            id: Some("d93398aa-14ab-447f-8d83-532dc4648fb9".to_string()),
+            name: Some("Complife Italia srl".to_string()),
+            address: Some("Via Fratelli Signorelli, 159".to_string()),
+            city: Some("Garbagnate Milanese (MI)".to_string()),
+            state: Some("".to_string()),
+            country: Some("Italy".to_string()),
+            zip: Some("20024".to_string()),
+        };
+
+        let ct_list = CentreList {
+            centres: vec![ct1, ct2],
+        };
+
         let partics = Participants {
             country_list: cy_list,
+            centre_list: Some(ct_list),
             participant_type_list: pt_list,
             inclusion: Some("1. Medically stable adults aged 18–80 years 2. Experienced a first-ever ischemic or hemorrhagic stroke".to_string()),
             age_range: Some("Senior".to_string()),
@@ -1637,7 +1767,12 @@ mod tests {
         plain_english_summary: Some("Background and study aims Based on clinical observations made...".to_string()), 
         primary_outcome: Some("Stye recurrence was determined by interview".to_string()), 
         primary_outcomes: po_list,
+        secondary_outcomes: Some(SecOutcomesList { outcome_measures: vec![] }),
+        secondary_outcome: Some("1. Swelling was measured using the Vernier calliper".to_string()),
         trial_website: Some("".to_string()),   
+        ethics_approval_required: Some("Old ethics approval format".to_string()),
+        ethics_committee_list: Some(EthicsCommitteeList { ethics_committees: vec![] }),
+        ethics_approval: Some("Approved 22/04/2013, Institutional Ethical Committee".to_string()),
     };
 
     let sec_num = vec![SecondaryNumber {
@@ -1686,8 +1821,21 @@ mod tests {
     };
 
 
+    let centre_list = CentreList {
+        centres: vec![Centre {
+            id: Some("1da42984-9eec-43cf-9246-401eeb8c7447".to_string()),
+            name: Some("Government Ayurveda College Hospital".to_string()),
+            address: Some("Department of Shalakyatantra".to_string()),
+            city: Some("Kerala".to_string()),
+            state: Some("".to_string()),
+            country: Some("India".to_string()),
+            zip: Some("695001".to_string()),
+        }],
+    };
+
     let partics = Participants {
         country_list: country_list,
+        centre_list: Some(centre_list),
         participant_type_list: partic_type_list, 
         inclusion: Some("1. Aged 10 - 50 years".to_string()),
         age_range: Some("Mixed".to_string()),
@@ -0,0 +1,198 @@
+use crate::AppError;
+use crate::setup::config_reader::ApiPars;
+use reqwest::{Client, StatusCode};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use rand::prelude::*;
+use log::warn;
+
+// A single http client is used for all calls to the ISRCTN API during a download.
+// Each request first waits for a token from a token bucket rate limiter, and
+// transient failures (timeouts, connection problems, 5xx and 429 responses)
+// are retried, with exponentially increasing (and jittered) delays between attempts.
+
+pub struct ApiClient {
+    client: Client,
+    limiter: TokenBucket,
+    max_retries: u32,
+    backoff_base_ms: u64,
+}
+
+impl ApiClient {
+    pub fn new(pars: &ApiPars) -> Result<Self, AppError> {
+
+        let client = Client::builder()
+            .user_agent(pars.user_agent.clone())
+            .timeout(Duration::from_secs(pars.timeout_secs))
+            .connect_timeout(Duration::from_secs(pars.connect_timeout_secs))
+            .build()
+            .map_err(|e| AppError::ReqwestError("Unable to build http client".to_string(), e))?;
+
+        Ok(ApiClient {
+            client,
+            limiter: TokenBucket::new(pars.requests_per_minute, pars.burst_size),
+            max_retries: pars.max_retries,
+            backoff_base_ms: pars.backoff_base_ms,
+        })
+    }
+
+    pub async fn get_text(&self, url: &str) -> Result<String, AppError> {
+
+        let mut attempt = 0;
+        loop {
+            self.limiter.acquire().await;
+
+            let res = match self.client.get(url).send().await {
+                Ok(response) => match response.error_for_status() {
+                    Ok(response) => response.text().await,
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e),
+            };
+
+            match res {
+                Ok(text) => return Ok(text),
+                Err(e) if attempt < self.max_retries && is_transient(&e) => {
+                    let delay = backoff_delay(self.backoff_base_ms, attempt, rand::rng().random_range(0..=self.backoff_base_ms));
+                    warn!("Transient error from api ({}), retrying in {} ms: {}", e, delay.as_millis(), url);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                },
+                Err(e) => return Err(AppError::ReqwestError(url.to_string(), e)),
+            }
+        }
+    }
+}
+
+
+fn is_transient(e: &reqwest::Error) -> bool {
+    if e.is_timeout() || e.is_connect() || e.is_body() {
+        return true;
+    }
+    match e.status() {
+        Some(s) => s.is_server_error() || s == StatusCode::TOO_MANY_REQUESTS,
+        None => false,
+    }
+}
+
+
+fn backoff_delay(base_ms: u64, attempt: u32, jitter_ms: u64) -> Duration {
+
+    // Delay doubles with each attempt, capped (at 2^6 times the base) to avoid
+    // absurdly long waits if a large number of retries has been configured.
+
+    let factor = 2u64.pow(attempt.min(6));
+    Duration::from_millis(base_ms * factor + jitter_ms)
+}
+
+
+struct TokenBucket {
+    state: Mutex<BucketState>,
+    capacity: f64,
+    refill_per_sec: f64,
+}
+
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(requests_per_minute: u32, burst_size: u32) -> Self {
+        let capacity = burst_size.max(1) as f64;
+        TokenBucket {
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                last_refill: Instant::now(),
+            }),
+            capacity,
+            refill_per_sec: requests_per_minute.max(1) as f64 / 60.0,
+        }
+    }
+
+    async fn acquire(&self) {
+        loop {
+            // The lock is never held across an await.
+
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                self.try_take(&mut state, Instant::now())
+            };
+            match wait {
+                None => return,
+                Some(d) => tokio::time::sleep(d).await,
+            }
+        }
+    }
+
+    fn try_take(&self, state: &mut BucketState, now: Instant) -> Option<Duration> {
+
+        // Refill according to the time elapsed, then either take a token (returning None)
+        // or return the time that must elapse before a token will be available.
+
+        let elapsed = now.saturating_duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        state.last_refill = now;
+
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            None
+        }
+        else {
+            Some(Duration::from_secs_f64((1.0 - state.tokens) / self.refill_per_sec))
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_backoff_doubles_each_attempt() {
+        assert_eq!(backoff_delay(1000, 0, 0), Duration::from_millis(1000));
+        assert_eq!(backoff_delay(1000, 1, 0), Duration::from_millis(2000));
+        assert_eq!(backoff_delay(1000, 3, 250), Duration::from_millis(8250));
+    }
+
+    #[test]
+    fn check_backoff_is_capped() {
+        assert_eq!(backoff_delay(100, 6, 0), Duration::from_millis(6400));
+        assert_eq!(backoff_delay(100, 20, 0), Duration::from_millis(6400));
+    }
+
+    #[test]
+    fn check_bucket_allows_burst_then_waits() {
+        let bucket = TokenBucket::new(60, 2);
+        let mut state = bucket.state.lock().unwrap();
+        let now = state.last_refill;
+
+        assert_eq!(bucket.try_take(&mut state, now), None);
+        assert_eq!(bucket.try_take(&mut state, now), None);
+        let wait = bucket.try_take(&mut state, now).unwrap();
+        assert_eq!(wait, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn check_bucket_refills_over_time() {
+        let bucket = TokenBucket::new(120, 1);
+        let mut state = bucket.state.lock().unwrap();
+        let now = state.last_refill;
+
+        assert_eq!(bucket.try_take(&mut state, now), None);
+        assert!(bucket.try_take(&mut state, now).is_some());
+        assert_eq!(bucket.try_take(&mut state, now + Duration::from_millis(500)), None);
+    }
+
+    #[test]
+    fn check_bucket_does_not_exceed_capacity() {
+        let bucket = TokenBucket::new(60, 2);
+        let mut state = bucket.state.lock().unwrap();
+        let now = state.last_refill + Duration::from_secs(600);
+
+        assert_eq!(bucket.try_take(&mut state, now), None);
+        assert_eq!(bucket.try_take(&mut state, now), None);
+        assert!(bucket.try_take(&mut state, now).is_some());
+    }
+}
@@ -0,0 +1,315 @@
+use std::path::Path;
+use std::sync::OnceLock;
+use regex::Regex;
+use serde::Deserialize;
+use crate::AppError;
+
+// The rules used to recognise the type of a secondary identifier. They are read
+// from identifier_rules.toml, in the same folder as app_config.toml, if that file
+// exists, and otherwise from rules/identifier_rules.toml (which is built into the
+// program). Each rule has a pattern, the type id and name given to identifiers that
+// match it, any steps needed to normalise the matched value, and examples of
+// identifiers with the values the rule should give for them. The rules are tried in
+// the order listed in the file, so more specific patterns must be listed before more
+// general ones.
+
+// The rules used by name, where the type of an identifier is given in the source data
+// (see id_rule), which must therefore be present in any rules file used.
+
+pub const REQUIRED_RULES: [&str; 6] = ["IRAS", "CPMS", "NIHR", "CTIS", "EudraCT", "NCT"];
+
+#[derive(Deserialize)]
+struct TomlRules {
+    rules: Vec<TomlRule>,
+}
+
+#[derive(Deserialize)]
+struct TomlRule {
+    name: String,
+    pattern: String,
+    type_id: i32,
+    type_name: String,
+    normalise: Option<Vec<String>>,
+    examples: Option<Vec<RuleExample>>,
+}
+
+// An example identifier, and the value the rule should give for it. If no value is
+// expected the rule should not match the identifier.
+
+#[derive(Deserialize, Debug, Clone)]
+struct RuleExample {
+    input: String,
+    expected: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NormaliseStep {
+    UpperCase,
+    RemoveSpaces,
+}
+
+#[derive(Debug)]
+pub struct IdentifierRule {
+    pub name: String,
+    pub type_id: i32,
+    pub type_name: String,
+    pattern: Regex,
+    normalise: Vec<NormaliseStep>,
+    examples: Vec<RuleExample>,
+}
+
+impl IdentifierRule {
+
+    fn from_toml(r: TomlRule) -> Result<IdentifierRule, String> {
+
+        let pattern = Regex::new(&r.pattern)
+            .map_err(|e| format!("Invalid pattern for identifier rule {}: {}", r.name, e))?;
+        let mut normalise = Vec::new();
+        for step in r.normalise.unwrap_or_default() {
+            normalise.push(match step.as_str() {
+                "upper_case" => NormaliseStep::UpperCase,
+                "remove_spaces" => NormaliseStep::RemoveSpaces,
+                _ => return Err(format!("Unknown normalise step '{}' for identifier rule {}", step, r.name)),
+            });
+        }
+        Ok(IdentifierRule {
+            name: r.name,
+            type_id: r.type_id,
+            type_name: r.type_name,
+            pattern,
+            normalise,
+            examples: r.examples.unwrap_or_default(),
+        })
+    }
+
+
+    pub fn find(&self, value: &str) -> Option<String> {
+
+        // Returns the normalised identifier if the pattern is found in the value. The 'id'
+        // group, if the pattern has one, is used in preference to the whole match.
+
+        let caps = self.pattern.captures(value)?;
+        let found = caps.name("id").unwrap_or_else(|| caps.get(0).unwrap());
+        Some(self.normalised(found.as_str().trim()))
+    }
+
+
+    pub fn find_whole(&self, value: &str) -> Option<String> {
+
+        // As find, but only if the pattern matches the whole of the value.
+
+        let m = self.pattern.find(value)?;
+        if m.start() == 0 && m.end() == value.len() {
+            self.find(value)
+        }
+        else {
+            None
+        }
+    }
+
+
+    fn normalised(&self, value: &str) -> String {
+        let mut v = value.to_string();
+        for step in &self.normalise {
+            v = match step {
+                NormaliseStep::UpperCase => v.to_uppercase(),
+                NormaliseStep::RemoveSpaces => v.replace(' ', ""),
+            };
+        }
+        v
+    }
+}
+
+
+fn read_rules(toml_string: &str) -> Result<Vec<IdentifierRule>, String> {
+
+    let toml_rules: TomlRules = toml::from_str(toml_string)
+        .map_err(|e| format!("Unable to read the identifier rules: {}", e))?;
+    toml_rules.rules.into_iter().map(IdentifierRule::from_toml).collect()
+}
+
+
+fn check_examples(rules: &[IdentifierRule]) -> Result<(), String> {
+
+    // Each example must give the expected value when tested against the whole set of
+    // rules, i.e. the rule it belongs to must be the first to match it. An example with
+    // no expected value must not be matched by its rule.
+
+    for rule in rules {
+        for ex in &rule.examples {
+            match &ex.expected {
+                Some(expected) => {
+                    let first = rules.iter().find_map(|r| r.find(&ex.input).map(|v| (r, v)));
+                    match first {
+                        Some((r, v)) if r.name == rule.name && &v == expected => {},
+                        Some((r, v)) => return Err(format!("Example '{}' of identifier rule {} gives '{}' from rule {}, rather than '{}'",
+                                                            ex.input, rule.name, v, r.name, expected)),
+                        None => return Err(format!("Example '{}' of identifier rule {} is not matched by any rule", ex.input, rule.name)),
+                    }
+                },
+                None => {
+                    if let Some(v) = rule.find(&ex.input) {
+                        return Err(format!("Example '{}' of identifier rule {} should not be matched, but gives '{}'", ex.input, rule.name, v));
+                    }
+                },
+            }
+        }
+    }
+    Ok(())
+}
+
+
+fn embedded_rules() -> Vec<IdentifierRule> {
+    read_rules(include_str!("../../rules/identifier_rules.toml")).unwrap()
+}
+
+
+fn check_required_rules(rules: &[IdentifierRule]) -> Result<(), String> {
+    let missing: Vec<&str> = REQUIRED_RULES.into_iter()
+        .filter(|n| !rules.iter().any(|r| r.name == *n)).collect();
+    if missing.is_empty() {
+        Ok(())
+    }
+    else {
+        Err(format!("The identifier rules must include rules named {}, but have none named {}",
+                    REQUIRED_RULES.join(", "), missing.join(", ")))
+    }
+}
+
+
+fn rules_from_file(rules_path: &Path) -> Result<Vec<IdentifierRule>, AppError> {
+
+    // Rules read from a file are checked against their examples, and for the rules used
+    // by name, before use, so that a mistake in an edited file stops the program rather
+    // than misclassifying identifiers (or failing part way through a download).
+
+    let toml_string = std::fs::read_to_string(rules_path)
+        .map_err(|e| AppError::IoReadErrorWithPath(e, rules_path.to_owned()))?;
+    let rules = read_rules(&toml_string)
+        .and_then(|rules| check_required_rules(&rules).map(|_| rules))
+        .and_then(|rules| check_examples(&rules).map(|_| rules))
+        .map_err(|e| AppError::ConfigurationError(e, format!("File ({}) may be malformed.", rules_path.display())))?;
+    Ok(rules)
+}
+
+
+static ID_RULES: OnceLock<Vec<IdentifierRule>> = OnceLock::new();
+
+
+pub fn load_id_rules(rules_path: &Path) -> Result<bool, AppError> {
+
+    // Reads the rules from the file given, if it exists, to be used instead of those built
+    // into the program. Returns true if the file was used. Has no effect once the rules
+    // have been used (and so fixed) for classifying identifiers.
+
+    if !rules_path.exists() {
+        return Ok(false);
+    }
+    let rules = rules_from_file(rules_path)?;
+    Ok(ID_RULES.set(rules).is_ok())
+}
+
+
+pub fn id_rules() -> &'static [IdentifierRule] {
+    ID_RULES.get_or_init(embedded_rules)
+}
+
+
+pub fn id_rule(name: &str) -> Option<&'static IdentifierRule> {
+
+    // Used where the type of an identifier is already known, e.g. from the
+    // type given in the source data, so that its type id and name are consistent
+    // with those of the same identifier type found by classification. The rules
+    // in REQUIRED_RULES are always present, as a rules file without them is rejected.
+
+    id_rules().iter().find(|r| r.name == name)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_rules_file_is_valid() {
+        let rules = embedded_rules();
+        let mut names: Vec<&str> = rules.iter().map(|r| r.name.as_str()).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), rules.len());
+        assert_eq!(check_required_rules(&rules), Ok(()));
+    }
+
+    #[test]
+    fn check_rule_examples() {
+
+        // Every rule in the file needs at least one example it should match, and all the
+        // examples should give the expected values (see check_examples).
+
+        let rules = embedded_rules();
+        for rule in &rules {
+            assert!(rule.examples.iter().any(|ex| ex.expected.is_some()), "No examples for identifier rule {}", rule.name);
+        }
+        assert_eq!(check_examples(&rules), Ok(()));
+    }
+
+    #[test]
+    fn check_invalid_rules_are_reported() {
+        let bad_pattern = "[[rules]]\nname = \"X\"\npattern = 'X(\\d'\ntype_id = 1\ntype_name = \"X ID\"\n";
+        assert!(read_rules(bad_pattern).unwrap_err().starts_with("Invalid pattern for identifier rule X"));
+        let bad_step = "[[rules]]\nname = \"X\"\npattern = 'X\\d'\ntype_id = 1\ntype_name = \"X ID\"\nnormalise = [\"lower_case\"]\n";
+        assert!(read_rules(bad_step).unwrap_err().starts_with("Unknown normalise step 'lower_case'"));
+    }
+
+    #[test]
+    fn check_failing_examples_are_reported() {
+        let rules = read_rules("[[rules]]\nname = \"X\"\npattern = 'X\\d'\ntype_id = 1\ntype_name = \"X ID\"\n\
+                                examples = [{ input = \"X1\", expected = \"X2\" }, { input = \"X3\" }]\n").unwrap();
+        assert_eq!(check_examples(&rules).unwrap_err(), "Example 'X1' of identifier rule X gives 'X1' from rule X, rather than 'X2'");
+        let rules = read_rules("[[rules]]\nname = \"X\"\npattern = 'X\\d'\ntype_id = 1\ntype_name = \"X ID\"\n\
+                                examples = [{ input = \"X3\" }]\n").unwrap();
+        assert_eq!(check_examples(&rules).unwrap_err(), "Example 'X3' of identifier rule X should not be matched, but gives 'X3'");
+    }
+
+    #[test]
+    fn check_rules_file_read_when_present() {
+
+        // The built in rules with a new rule added to the end.
+
+        let rules_path = std::env::temp_dir().join("dl_isrctn_test_identifier_rules.toml");
+        let embedded = include_str!("../../rules/identifier_rules.toml");
+        let with_new_rule = |example: &str| format!("{}\n[[rules]]\nname = \"X\"\npattern = 'X\\d'\n\
+                    type_id = 1\ntype_name = \"X ID\"\nexamples = [{}]\n", embedded, example);
+        std::fs::write(&rules_path, with_new_rule("{ input = \"X1\", expected = \"X1\" }")).unwrap();
+        let rules = rules_from_file(&rules_path).unwrap();
+        assert_eq!(rules.len(), embedded_rules().len() + 1);
+        assert_eq!(rules.last().unwrap().find("ref X7"), Some("X7".to_string()));
+        std::fs::write(&rules_path, with_new_rule("{ input = \"Y1\", expected = \"Y1\" }")).unwrap();
+        assert!(matches!(rules_from_file(&rules_path), Err(AppError::ConfigurationError(_, _))));
+        std::fs::remove_file(&rules_path).unwrap();
+        assert!(!load_id_rules(&rules_path).unwrap());
+    }
+
+    #[test]
+    fn check_rules_file_without_required_rules_rejected() {
+
+        // A file that is valid in itself, but leaves out a rule used by name.
+
+        let rules_path = std::env::temp_dir().join("dl_isrctn_test_required_rules.toml");
+        let embedded = include_str!("../../rules/identifier_rules.toml");
+        let without_nct: Vec<&str> = embedded.split("[[rules]]").filter(|r| !r.contains("name = \"NCT\"")).collect();
+        std::fs::write(&rules_path, without_nct.join("[[rules]]")).unwrap();
+        match rules_from_file(&rules_path) {
+            Err(AppError::ConfigurationError(e, _)) => assert!(e.ends_with("but have none named NCT"), "{}", e),
+            _ => panic!("a rules file without an NCT rule should be rejected"),
+        }
+        std::fs::remove_file(&rules_path).unwrap();
+    }
+
+    #[test]
+    fn check_whole_match_required_by_find_whole() {
+        let rule = id_rule("EudraCT").unwrap();
+        assert_eq!(rule.find_whole("2004-001234-56"), Some("2004-001234-56".to_string()));
+        assert_eq!(rule.find_whole("EudraCT 2004-001234-56"), None);
+    }
+}
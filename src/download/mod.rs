@@ -1,25 +1,38 @@
 mod processor;
 mod support_fns;
+mod id_rules;
+mod api_client;
+mod windows;
+mod pipeline;
+mod reconcile;
+
+pub use id_rules::load_id_rules;
 
 use crate::setup::db_pars::get_db_pool;
 use crate::data_models::xml_models;
 use crate::data_models::json_models;
+use crate::data_models::json_schema;
 use crate::recording::processes::MonitorRepo;
+use crate::recording::events::EventRepo;
 use crate::AppError;
 use crate::base_types::*;
 
 use chrono::{NaiveDate, Days};
-use xml_models::{AllTrials, FullTrial, TrialsCount};
+use xml_models::{AllTrials, TrialsCount};
 use quick_xml::de;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use serde_json::to_string_pretty;
-use std::{fs, thread, time};
-use rand::prelude::*;
+use std::fs;
+use std::sync::Arc;
+use api_client::ApiClient;
+use windows::*;
+use pipeline::{PageSource, PipelineItem, PipelineContext, Checkpointing, run_pipeline};
+use tokio::sync::mpsc;
 use log::info;
 
 
-pub async fn download_data(params: &InitParams, dl_id:i32) -> Result<DownloadResult, AppError> {
+pub async fn download_data(params: &InitParams, dl_id:i32, prior_res: DownloadResult, events: &EventRepo) -> Result<DownloadResult, AppError> {
 
     // The base url, json file folder, log folder, and start and end dates have
     // already been checked as being present and reasonable.
@@ -39,12 +52,40 @@ pub async fn download_data(params: &InitParams, dl_id:i32) -> Result<DownloadRes
     // in the specified period. The parameter used is therefor 'dateApplied' rather than 'lastEdited'. These
     // optrions are chiefly used when doing a full reconstruction of the dataset.
 
-    // In either case, each period is broken up into periods of 4 days. There does not appear to be
-    // a way to rank or order results and select from within a returned set, so record sets are returned 'as is'.
-    // If the number of available records for a selected 4-day period is > 100 records the call is
-    // broken down into calls for individual days.
+    // In either case the overall period is walked through in windows, initially of 4 days. The number of
+    // records in each window is checked first. There does not appear to be a way to rank or order results and
+    // select from within a returned set, so if that number is greater than the page size (by default 100) the
+    // window is split in half, and each half checked again, recursively, until each range can be requested
+    // in a single call. 'lastEdited' ranges can be split into parts of a day, down to a minimum of a minute,
+    // 'dateApplied' ranges only into single days. If a range cannot be split further all its records are
+    // requested together. Empty windows are followed by windows twice as long (up to 64 days), so that quiet
+    // stretches need few calls, while windows that had to be split are followed by windows half as long.
+
+    // The windows are walked through while the pages already listed are being fetched, processed and
+    // written, by a single pipeline for the whole download. As the pages are written the event record
+    // is updated with the end date of the latest window all of whose pages (and those of all earlier
+    // windows) have been written, and the totals for those pages, so that an interrupted download can be
+    // resumed (with --resume) from that point. In that case the start date is the last recorded end date,
+    // and the totals are added to prior_res.
+
+    // If the download type is 'FromXml' no dates are involved - instead the xml pages saved
+    // during a previous live download (using --save-xml) are processed, without any API access.
+
+    // A reconciliation ('Reconcile') only lists the ids of the studies in the registry,
+    // and writes no files - see the reconcile module.
+
+    // The json schema describing the files is (re)written alongside them in all other cases.
+
+    if params.download_type == DownloadType::Reconcile {
+        let monitor = MonitorRepo::new(get_db_pool("source").await?);
+        return reconcile::reconcile_ids(params, &monitor).await;
+    }
+    json_schema::write_schema_file(&params.json_data_path)?;
+    if params.download_type == DownloadType::FromXml {
+        return replay_saved_xml(params, dl_id).await;
+    }
 
-    let mut sd = match params.start_date {
+    let sd = match params.start_date {
         Some(nd) => nd,
         None => {return Err(AppError::MissingProgramParameter("Start date required but not provided".to_string()))},
     };
@@ -53,181 +94,198 @@ pub async fn download_data(params: &InitParams, dl_id:i32) -> Result<DownloadRes
         None => {return Err(AppError::MissingProgramParameter("End date required but not provided".to_string()))},
     };
 
-    let base_url = params.api_base_url.clone();
+    let range_parameter = if params.download_type == DownloadType::Recent
+                                || params.download_type == DownloadType::UdBetweenDates {
+        "lastEdited"
+    }
+    else {
+        "dateApplied"
+    };
+
     let src_pool = get_db_pool("source").await?; // pool for the source specific db
     let monitor = MonitorRepo::new(src_pool.clone());  // events repo object
-    let mut res = DownloadResult::new();
-    
-    while sd < edate  {
+    let api = Arc::new(ApiClient::new(&params.api_pars)?);  // shared, rate limited, http client
+    let ctx = PipelineContext {
+        api: Some(api),
+        save_xml_path: params.save_xml_path.clone(),
+        json_data_path: params.json_data_path.clone(),
+        max_concurrent: params.api_pars.max_concurrent_requests,
+        archive: params.archive,
+    };
+    let checkpointing = match params.is_test {
+        true => None,
+        false => Some(Checkpointing { events: EventRepo::new(events.pool.clone()), prior_res: prior_res.clone() }),
+    };
 
-        // For each pass, set end date to be 4 days later than start date.
-        // If that goes past the overall end date set end date back to the overall end date.
+    let (tx, rx) = mpsc::channel::<PipelineItem>(ctx.max_concurrent * 2);
+    let (walked, piped) = tokio::join!(
+        list_pages(params, &ctx, range_parameter, sd, edate, tx),
+        run_pipeline(&ctx, rx, dl_id, &monitor, checkpointing),
+    );
+    let res = prior_res + piped?;
+    walked?;
 
-        let mut ed = sd.checked_add_days(Days::new(4)).unwrap();  // unwrap should be safe!
-        if ed > edate {
-            ed = edate // ensure does not go beyond end of range
-        }
-
-        // Establish api url for these dates.
+    info!("{} records checked in total. {} Files written ({} of them new), {} unchanged", res.num_checked, res.num_downloaded, res.num_added, res.num_unchanged);
+    Ok(res)
+}
 
-        let range_parameter: &str;
-        if params.download_type == DownloadType::Recent || params.download_type == DownloadType::UdBetweenDates {
-            range_parameter = "lastEdited";
-        }
-        else {
-            range_parameter = "dateApplied";
-        }
 
-        let start_date_param = sd.format("%Y-%m-%d").to_string();
-        let query_start_param = format!("{}%20GE%20{}T00:00:00%20AND%20", range_parameter, start_date_param);
-        let end_date_param = ed.format("%Y-%m-%d").to_string();
-        let query_end_param = format!("{}%20LT%20{}T00:00:00%20&limit=", range_parameter, end_date_param);
-        let dated_url = format!("{}{}{}", base_url, query_start_param, query_end_param);
+async fn list_pages(params: &InitParams, ctx: &PipelineContext, range_parameter: &str, mut sd: NaiveDate,
+                    edate: NaiveDate, tx: mpsc::Sender<PipelineItem>) -> Result<(), AppError>
+{
+    // Each window is divided into the ranges that can each be requested in a single call.
+    // Ranges still to be checked are held on a stack, with the earlier half of any split
+    // range on top, so that pages are listed in date order. Each page is passed to the pipeline
+    // as soon as it is listed, followed by the window's end date once all its pages have been.
+    // If the pipeline has stopped (it reports its own error) the listing simply ends.
 
-        // Initially just get record count.
+    let api = ctx.api.as_deref().ok_or_else(|| AppError::MissingProgramParameter("api client".to_string()))?;
+    let page_size = params.api_pars.page_size;
+    let min_span = min_span(range_parameter);
+    let mut window_days = BASE_WINDOW_DAYS;
 
-        let url = format!("{}{}", dated_url, 1);
-        let record_num = get_study_count(&url).await?;
+    while sd < edate  {
 
-        // If over 100 records split processing to by day, else process all.
+        // Set the window end date, but ensure it does not go beyond end of overall range.
 
-        if record_num > 0 {
-            if record_num > 100 {    // Split the (up to) 4 days up into single days
-                let mut d = sd;
-                while d < ed {
-                    let this_res = process_single_day(params, range_parameter, &d, dl_id, &monitor).await?;
-                    info!("For single day {}, records checked:{}", d, this_res.num_checked);
-                    res = res + this_res;
-                    d = d.checked_add_days(Days::new(1)).unwrap();
-                }
-            }
-            else {    // Process all records.
-                let url = format!("{}{}", dated_url, 100);
-                let studies: AllTrials = get_studies(&url).await?;
-                let this_res = process_studies(params, studies.full_trials, dl_id, &monitor).await?;
-                info!("For period GE {}, to LT {}, records checked:{}", start_date_param, end_date_param, this_res.num_checked);
-                res = res + this_res;
-            }
-        }
-        else {
-            info!("For period GE {}, to LT {}, no records found", start_date_param, end_date_param);
+        let mut ed = sd.checked_add_days(Days::new(window_days)).unwrap();  // unwrap should be safe!
+        if ed > edate {
+            ed = edate
         }
-        sd = ed;    // make the start date the old end date
-    }
-
-    info!("{} records checked in total. {} Files written ({} of them new)", res.num_checked, res.num_downloaded, res.num_added);
-    Ok(res)
-}
-
-
-async fn process_single_day(params: &InitParams, range_parameter: &str, date: &NaiveDate, dl_id: i32, monitor: &MonitorRepo) -> Result<DownloadResult, AppError>
-{
-    let date_param = date.format("%Y-%m-%d").to_string();
-    let query_start_param = format!("{}%20GE%20{}T00:00:00%20AND%20", range_parameter, date_param);
-    let query_end_param = format!("{}%20LT%20{}T23:59:59%20&limit=", range_parameter, date_param);
 
-    // See how many records there are this day.
+        let mut outcome = WindowOutcome::Empty;
+        let mut window_records = 0;
+        let mut ranges = vec![(sd.and_hms_opt(0, 0, 0).unwrap(), ed.and_hms_opt(0, 0, 0).unwrap())];
 
-    let base_url = params.api_base_url.clone();
-    let url = format!("{}{}{}{}", base_url, query_start_param, query_end_param, 1);
-    let limit = get_study_count(&url).await?;
+        while let Some((from, to)) = ranges.pop() {
 
-    if limit > 0 {
+            let url = format!("{}{}", params.api_base_url, range_query(range_parameter, from, to, 1));
+            let record_num = get_study_count(api, &url).await?;
+            if record_num == 0 {
+                continue;
+            }
 
-        // Get the full set of records (i.e. set limit to be all the records available).
+            if record_num > page_size
+                && let Some(mid) = split_point(from, to, min_span) {
+                outcome = WindowOutcome::Split;
+                ranges.push((mid, to));
+                ranges.push((from, mid));
+            }
+            else {
+                if outcome == WindowOutcome::Empty {
+                    outcome = WindowOutcome::Single;
+                }
+                window_records += record_num;
+                let page = PageSource::Api {
+                    url: format!("{}{}", params.api_base_url, range_query(range_parameter, from, to, record_num)),
+                    page_name: page_name(range_parameter, from, to),
+                };
+                if tx.send(PipelineItem::Page(page)).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
 
-        let url = format!("{}{}{}{}", base_url, query_start_param, query_end_param, limit);
-        let studies: AllTrials = get_studies(&url).await?;
-        let res = process_studies(params, studies.full_trials, dl_id, monitor).await?;
+        if outcome == WindowOutcome::Empty {
+            info!("For period GE {}, to LT {}, no records found", sd, ed);
+        }
+        else {
+            info!("For period GE {}, to LT {}, records listed:{}", sd, ed, window_records);
+        }
+        if tx.send(PipelineItem::WindowEnd(ed)).await.is_err() {
+            return Ok(());
+        }
 
-        Ok(res)
-    }
-    else {
-        Ok(DownloadResult::new())
+        window_days = next_window_days(window_days, outcome);
+        sd = ed;    // make the start date the old end date
     }
+    Ok(())
 }
 
 
-async fn get_study_count(url: &String) -> Result<i32, AppError> {
-
-    let response = reqwest::get(url.clone()).await
-        .map_err(|e| AppError::ReqwestError(url.clone(), e))?;
-    pause(500, 1000);  // Add a pause - random value between 0.5 and 1.5 seconds.
+async fn get_study_count(api: &ApiClient, url: &String) -> Result<i32, AppError> {
 
     // Extract api text, deserialise it to the very simple TrialsCount xml model
 
-    let xml_content = response.text().await
-        .map_err(|e| AppError::ReqwestError(url.clone(), e))?;
+    let xml_content = api.get_text(url).await?;
     let trials_count: TrialsCount = de::from_str(&xml_content)
         .map_err(|e| AppError::QuickXMLError(url.clone(), e))?;
     Ok(trials_count.total_count)
 }
 
 
-async fn get_studies(url: &String) -> Result<AllTrials, AppError> {
-
-    let response = reqwest::get(url.clone()).await
-        .map_err(|e| AppError::ReqwestError(url.clone(), e))?;
-    pause(500, 1000); // Add a pause - random value between 0.5 and 1.5 seconds.
+async fn get_studies(api: &ApiClient, url: &String, save_xml_path: &Option<PathBuf>, page_name: &str) -> Result<AllTrials, AppError> {
 
-    // Extract api text and return deserialised AllTrials xml model
+    // Extract api text, save it if required, and return deserialised AllTrials xml model
 
-    let xml_content = response.text().await
-        .map_err(|e| AppError::ReqwestError(url.clone(), e))?;
+    let xml_content = api.get_text(url).await?;
+    if let Some(xml_folder) = save_xml_path {
+        save_xml_page(&xml_content, xml_folder, page_name)?;
+    }
     de::from_str(&xml_content)
         .map_err(|e| AppError::QuickXMLError(url.clone(), e))
 }
 
 
-fn pause(base_num:u64, range_num:u64) -> () {
+fn save_xml_page(xml_content: &str, xml_folder: &Path, page_name: &str) -> Result<(), AppError> {
 
-    // Add a pause, e.g. after any api access. Duration is a random value 
-    // between base_num and (base_num + max(random component)) milliseconds
+    // Pages are named by the range parameter and dates used to request them,
+    // so a later download of the same period replaces the earlier page.
 
-    let mut rng = rand::rng();
-    let random_component = &rng.random_range(1..=range_num);
-    let millis = base_num + random_component;
-    let pause = time::Duration::from_millis(millis);
-    thread::sleep(pause);
+    let file_path: PathBuf = xml_folder.join(format!("{}.xml", page_name));
+    fs::write(&file_path, xml_content)
+        .map_err(|e| AppError::IoWriteErrorWithPath(e, file_path))
 }
 
 
-async fn process_studies(params: &InitParams, studies: Vec<FullTrial>, dl_id: i32, monitor: &MonitorRepo) -> Result<DownloadResult, AppError> {
-
-    let mut res = DownloadResult::new();
+async fn replay_saved_xml(params: &InitParams, dl_id: i32) -> Result<DownloadResult, AppError> {
 
-    // Iterate through studies, the vector of FullTrials
-    // For each, call the process_studyroutine that goes through the xml
-    // derived structure and which produces a much more mdr compliant model
-    // That includes tidying up many of the fields, removing spaces and carriage returns...
-    // Once that model has been returned Write it out as a json file, for
-    // later import and further processing.
+    // Each xml file in the folder is read and deserialised exactly as if
+    // it had just been returned from the API, and the studies processed in the usual way.
 
-    for s in studies {
-        res.num_checked += 1;
-        let json_study = processor::process_study(s)?;
-
-        let sd_sid = &json_study.sd_sid;
-        let record_date = &json_study.registration.last_updated;
-        let remote_url = format!("https://www.isrctn.com/{}", sd_sid.clone());
+    let xml_folder = match &params.from_xml_path {
+        Some(p) => p,
+        None => {return Err(AppError::MissingProgramParameter("Folder of saved xml required but not provided".to_string()))},
+    };
 
-        let full_path = write_out_file(&sd_sid, &json_study, &params.json_data_path).await?;
+    let src_pool = get_db_pool("source").await?; // pool for the source specific db
+    let monitor = MonitorRepo::new(src_pool.clone());  // events repo object
+    let ctx = PipelineContext {
+        api: None,
+        save_xml_path: None,
+        json_data_path: params.json_data_path.clone(),
+        max_concurrent: params.api_pars.max_concurrent_requests,
+        archive: params.archive,
+    };
 
-        res.num_downloaded += 1;
-        let added = monitor.update_dl_details(sd_sid, &remote_url, dl_id,
-                    record_date, &full_path).await?;
-        if added {
-            res.num_added +=1;
+    let mut xml_files: Vec<PathBuf> = fs::read_dir(xml_folder)
+        .map_err(|e| AppError::IoReadErrorWithPath(e, xml_folder.clone()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "xml"))
+        .collect();
+    xml_files.sort();
+    info!("{} saved xml files found", xml_files.len());
+
+    let (tx, rx) = mpsc::channel::<PipelineItem>(ctx.max_concurrent * 2);
+    let feed = async move {
+        for file in xml_files {
+            if tx.send(PipelineItem::Page(PageSource::SavedFile(file))).await.is_err() {
+                break;      // the pipeline has stopped, and reports its own error
+            }
         }
-    }
+    };
+    let (_, piped) = tokio::join!(feed, run_pipeline(&ctx, rx, dl_id, &monitor, None));
+    let res = piped?;
+
+    info!("{} records checked in total. {} Files written ({} of them new), {} unchanged", res.num_checked, res.num_downloaded, res.num_added, res.num_unchanged);
     Ok(res)
 }
 
 
-pub async fn write_out_file(sd_sid: &String, t: &json_models::Study, json_folder: &PathBuf) -> Result<PathBuf, AppError> {
+pub fn write_out_file(sd_sid: &String, t: &json_models::Study, json_folder: &PathBuf) -> Result<PathBuf, AppError> {
 
     // Writes out the file with the correct name to the correct folder, as indented json.
-    // Called from the process_studies function.
+    // Called from the pipeline's writer, on a blocking thread.
     // Returns the full file path as constructed.
 
     let reg_year_string = match &t.registration.date_id_assigned {
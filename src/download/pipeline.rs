@@ -0,0 +1,390 @@
+use super::{processor, write_out_file, get_studies, api_client::ApiClient};
+use crate::data_models::xml_models::{AllTrials, FullTrial};
+use crate::data_models::json_models::Study;
+use crate::recording::processes::{MonitorRepo, DlRecord, HistoryRecord, ChangeRecord};
+use crate::recording::events::EventRepo;
+use crate::data_models::study_diff;
+use crate::import::migration;
+use crate::base_types::DownloadResult;
+use crate::AppError;
+use chrono::NaiveDate;
+use log::warn;
+use quick_xml::de;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+// The download is carried out as a single bounded concurrent pipeline, fed with pages (and
+// the ends of the windows the pages come from) through a bounded channel, so that the pages
+// of later windows are being listed while those of earlier ones are fetched and written:
+// - up to max_concurrent_requests fetcher tasks, each obtaining a page (from the API, sharing the
+//   client and therefore its rate limiter, or from a saved xml file) and passing its trials to a
+//   blocking worker thread, where they are processed into the json model,
+// - a single writer task, receiving the processed studies through a bounded channel, and writing
+//   each batch to json files and then to mn.source_data. Studies whose content has not changed
+//   since their file was last written are neither re-written nor recorded again. In archive mode
+//   each distinct version of each study is also added to mn.source_data_history. The changes
+//   between the existing and new versions of any changed study are recorded in mn.study_changes.
+// Pages may be written in any order, so the download event is only checkpointed (if required)
+// with the end of the latest window whose pages, and those of all earlier windows, have all
+// been written, along with the totals for those pages. The pipeline returns once every page
+// sent to it has been written, or on the first error.
+
+pub enum PageSource {
+    Api { url: String, page_name: String },
+    SavedFile(PathBuf),
+}
+
+pub enum PipelineItem {
+    Page(PageSource),
+    WindowEnd(NaiveDate),   // all the pages of the window ending on this date have been sent
+}
+
+enum WriterItem {
+    Studies(usize, Vec<Study>),     // the studies of the page with this sequence number
+    WindowEnd(usize, NaiveDate),    // the number of pages sent before the window's end
+}
+
+pub struct Checkpointing {
+    pub events: EventRepo,
+    pub prior_res: DownloadResult,
+}
+
+pub struct PipelineContext {
+    pub api: Option<Arc<ApiClient>>,
+    pub save_xml_path: Option<PathBuf>,
+    pub json_data_path: PathBuf,
+    pub max_concurrent: usize,
+    pub archive: bool,
+}
+
+
+pub async fn run_pipeline(ctx: &PipelineContext, mut pages: mpsc::Receiver<PipelineItem>, dl_id: i32,
+                          monitor: &MonitorRepo, checkpointing: Option<Checkpointing>) -> Result<DownloadResult, AppError> {
+
+    let (tx, mut rx) = mpsc::channel::<WriterItem>(ctx.max_concurrent);
+
+    let json_data_path = ctx.json_data_path.clone();
+    let archive = ctx.archive;
+    let writer_monitor = monitor.clone();
+    let writer = tokio::spawn(async move {
+        let mut res = DownloadResult::new();
+        let mut tracker = CheckpointTracker::new();
+        while let Some(item) = rx.recv().await {
+            match item {
+                WriterItem::Studies(seq, studies) => {
+                    let page_res = write_batch(studies, &json_data_path, archive, dl_id, &writer_monitor).await?;
+                    res = res + page_res.clone();
+                    tracker.page_written(seq, page_res);
+                },
+                WriterItem::WindowEnd(num_pages, end_date) => tracker.window_listed(num_pages, end_date),
+            }
+            if let (Some(c), Some((end_date, written_res))) = (&checkpointing, tracker.take_checkpoint()) {
+                c.events.checkpoint_dl_event(dl_id, end_date, &(c.prior_res.clone() + written_res)).await?;
+            }
+        }
+        Ok::<DownloadResult, AppError>(res)
+    });
+
+    let mut fetchers = JoinSet::new();
+    let mut seq = 0;
+    while let Some(item) = pages.recv().await {
+
+        let page = match item {
+            PipelineItem::Page(page) => page,
+            PipelineItem::WindowEnd(end_date) => {
+                if tx.send(WriterItem::WindowEnd(seq, end_date)).await.is_err() {
+                    break;     // writer has stopped - its error is reported below
+                }
+                continue;
+            },
+        };
+
+        // Keep the number of fetchers in flight within the limit.
+
+        if fetchers.len() >= ctx.max_concurrent
+            && let Some(r) = fetchers.join_next().await {
+            r??;
+        }
+
+        let api = ctx.api.clone();
+        let save_xml_path = ctx.save_xml_path.clone();
+        let tx = tx.clone();
+        fetchers.spawn(async move {
+            if tx.is_closed() {
+                return Ok(());     // writer has stopped - its error is reported below
+            }
+            let trials = fetch_page(api.as_deref(), page, &save_xml_path).await?;
+            let studies = tokio::task::spawn_blocking(move || process_trials(trials)).await??;
+            let _ = tx.send(WriterItem::Studies(seq, studies)).await;     // fails only if the writer has stopped
+            Ok::<(), AppError>(())
+        });
+        seq += 1;
+    }
+    drop(tx);   // writer finishes once all fetchers have sent their studies
+
+    while let Some(r) = fetchers.join_next().await {
+        r??;
+    }
+    writer.await?
+}
+
+
+struct CheckpointTracker {
+    written: BTreeMap<usize, DownloadResult>,       // pages written after a gap in the sequence
+    num_complete: usize,                            // pages 0 to num_complete - 1 are all written
+    complete_res: DownloadResult,                   // the totals for those pages
+    window_ends: VecDeque<(usize, NaiveDate)>,      // windows not yet complete, in order
+    checkpoint: Option<(NaiveDate, DownloadResult)>,
+}
+
+impl CheckpointTracker {
+
+    fn new() -> Self {
+        CheckpointTracker {
+            written: BTreeMap::new(),
+            num_complete: 0,
+            complete_res: DownloadResult::new(),
+            window_ends: VecDeque::new(),
+            checkpoint: None,
+        }
+    }
+
+    fn page_written(&mut self, seq: usize, res: DownloadResult) {
+        self.written.insert(seq, res);
+        self.advance();
+    }
+
+    fn window_listed(&mut self, num_pages: usize, end_date: NaiveDate) {
+        self.window_ends.push_back((num_pages, end_date));
+        self.advance();
+    }
+
+    fn advance(&mut self) {
+
+        // Moves through the pages written in sequence, noting the end of each window reached
+        // (with the totals at that point), until the next page in the sequence is not yet written.
+
+        loop {
+            while let Some(&(num_pages, end_date)) = self.window_ends.front()
+                && num_pages <= self.num_complete {
+                self.window_ends.pop_front();
+                self.checkpoint = Some((end_date, self.complete_res.clone()));
+            }
+            match self.written.remove(&self.num_complete) {
+                Some(res) => {
+                    self.complete_res = self.complete_res.clone() + res;
+                    self.num_complete += 1;
+                },
+                None => break,
+            }
+        }
+    }
+
+    fn take_checkpoint(&mut self) -> Option<(NaiveDate, DownloadResult)> {
+        self.checkpoint.take()
+    }
+}
+
+
+async fn fetch_page(api: Option<&ApiClient>, page: PageSource, save_xml_path: &Option<PathBuf>) -> Result<Vec<FullTrial>, AppError> {
+
+    match page {
+        PageSource::Api { url, page_name } => {
+            let api = api.ok_or_else(|| AppError::MissingProgramParameter("api client".to_string()))?;
+            let studies: AllTrials = get_studies(api, &url, save_xml_path, &page_name).await?;
+            Ok(studies.full_trials)
+        },
+        PageSource::SavedFile(file_path) => {
+            let xml_content = fs::read_to_string(&file_path)
+                .map_err(|e| AppError::IoReadErrorWithPath(e, file_path.clone()))?;
+            let studies: AllTrials = de::from_str(&xml_content)
+                .map_err(|e| AppError::QuickXMLError(file_path.to_string_lossy().to_string(), e))?;
+            Ok(studies.full_trials)
+        },
+    }
+}
+
+
+fn process_trials(trials: Vec<FullTrial>) -> Result<Vec<Study>, AppError> {
+
+    // Call the process_study routine that goes through the xml derived structure
+    // and which produces a much more mdr compliant model. That includes tidying up
+    // many of the fields, removing spaces and carriage returns...
+
+    trials.into_iter().map(processor::process_study).collect()
+}
+
+
+async fn write_batch(studies: Vec<Study>, json_data_path: &Path, archive: bool, dl_id: i32, monitor: &MonitorRepo) -> Result<DownloadResult, AppError> {
+
+    // Write out each study as a json file, for later import and further processing,
+    // then record the batch in the monitor table. A study is skipped if the hash of its
+    // content matches that recorded when its file was last written (and the file is still
+    // there), so that its last_downloaded date is not changed, and it is not re-imported.
+    // Archived versions include those of unchanged studies, as these may not yet be in
+    // the archive (if they were last written without archiving). For a study that has changed,
+    // the existing file is compared with the new version before being overwritten.
+
+    let num_checked = studies.len() as i32;
+    let sd_sids: Vec<&str> = studies.iter().map(|s| s.sd_sid.as_str()).collect();
+    let stored_files = monitor.get_stored_files(&sd_sids).await?;
+
+    let json_folder = json_data_path.to_path_buf();
+    let (recs, history_recs, change_recs) = tokio::task::spawn_blocking(move || {
+        let mut recs = Vec::with_capacity(studies.len());
+        let mut history_recs = Vec::new();
+        let mut change_recs = Vec::new();
+        for s in &studies {
+            let content_hash = content_hash(s)?;
+            if archive {
+                history_recs.push(HistoryRecord {
+                    sd_sid: s.sd_sid.clone(),
+                    record_version: s.registration.version.clone(),
+                    last_updated: s.registration.last_updated.clone(),
+                    content_hash: content_hash.clone(),
+                    study_json: serde_json::to_string(s)?,
+                });
+            }
+            let stored_file = stored_files.get(&s.sd_sid)
+                .filter(|f| f.local_path.as_ref().is_some_and(|p| Path::new(p).exists()));
+            if let Some(f) = stored_file {
+                if f.content_hash.as_deref() == Some(content_hash.as_str()) {
+                    continue;
+                }
+                if let Some(p) = &f.local_path {
+                    change_recs.extend(find_changes(p, s));
+                }
+            }
+            let full_path = write_out_file(&s.sd_sid, s, &json_folder)?;
+            recs.push(DlRecord {
+                sd_sid: s.sd_sid.clone(),
+                remote_url: format!("https://www.isrctn.com/{}", s.sd_sid),
+                record_date: s.registration.last_updated.clone(),
+                full_path,
+                content_hash,
+            });
+        }
+        Ok::<(Vec<DlRecord>, Vec<HistoryRecord>, Vec<ChangeRecord>), AppError>((recs, history_recs, change_recs))
+    }).await??;
+
+    let num_added = if recs.is_empty() {0} else {monitor.update_dl_details_batch(&recs, dl_id).await?};
+    if !history_recs.is_empty() {
+        monitor.add_history_batch(&history_recs, dl_id).await?;
+    }
+    if !change_recs.is_empty() {
+        monitor.add_changes_batch(&change_recs, dl_id).await?;
+    }
+
+    Ok(DownloadResult {
+        num_checked,
+        num_downloaded: recs.len() as i32,
+        num_added,
+        num_unchanged: num_checked - recs.len() as i32,
+    })
+}
+
+
+fn find_changes(old_path: &str, new: &Study) -> Vec<ChangeRecord> {
+
+    // A failure to read or compare the existing file is logged, but does not stop the download.
+
+    let old_path = Path::new(old_path);
+    let changes = fs::read_to_string(old_path)
+        .map_err(|e| AppError::IoReadErrorWithPath(e, old_path.to_path_buf()))
+        .and_then(|json_data| migration::study_from_json(&json_data, old_path))
+        .and_then(|old| study_diff::diff_studies(&old, new));
+    match changes {
+        Ok(changes) => changes.into_iter().map(|c| ChangeRecord { sd_sid: new.sd_sid.clone(), change: c }).collect(),
+        Err(e) => {
+            warn!("Changes to {} could not be identified: {}", new.sd_sid, e);
+            Vec::new()
+        }
+    }
+}
+
+
+fn content_hash(s: &Study) -> Result<String, AppError> {
+
+    // The hash (sha256, as hex) of the study's canonical json, i.e. with its keys in sorted
+    // order and without the download date-time, which differs on every download.
+
+    hash_of_value(serde_json::to_value(s)?)
+}
+
+
+fn hash_of_value(mut value: serde_json::Value) -> Result<String, AppError> {
+
+    if let Some(obj) = value.as_object_mut() {
+        obj.remove("downloaded");
+    }
+    let canonical = serde_json::to_vec(&value)?;
+    Ok(format!("{:x}", Sha256::digest(&canonical)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn page_res(num_checked: i32) -> DownloadResult {
+        DownloadResult { num_checked, num_downloaded: num_checked, num_added: 0, num_unchanged: 0 }
+    }
+
+    fn checkpoint(tracker: &mut CheckpointTracker) -> Option<(String, i32)> {
+        tracker.take_checkpoint().map(|(d, r)| (d.to_string(), r.num_checked))
+    }
+
+    #[test]
+    fn check_checkpoint_waits_for_all_pages_of_window() {
+        let date = |d: u32| NaiveDate::from_ymd_opt(2025, 1, d).unwrap();
+        let mut tracker = CheckpointTracker::new();
+        tracker.window_listed(0, date(4));                  // an empty window
+        assert_eq!(checkpoint(&mut tracker), Some(("2025-01-04".to_string(), 0)));
+        tracker.page_written(1, page_res(20));              // pages 0 and 1 in the next window
+        tracker.window_listed(2, date(8));
+        assert_eq!(checkpoint(&mut tracker), None);         // page 0 not yet written
+        tracker.page_written(2, page_res(5));               // page 2 in a window not yet complete
+        tracker.page_written(0, page_res(10));
+        assert_eq!(checkpoint(&mut tracker), Some(("2025-01-08".to_string(), 30)));
+        tracker.window_listed(4, date(12));
+        assert_eq!(checkpoint(&mut tracker), None);         // page 3 not yet written
+        tracker.page_written(3, page_res(1));
+        assert_eq!(checkpoint(&mut tracker), Some(("2025-01-12".to_string(), 36)));
+    }
+
+    #[test]
+    fn check_checkpoint_moves_to_latest_complete_window() {
+        let date = |d: u32| NaiveDate::from_ymd_opt(2025, 1, d).unwrap();
+        let mut tracker = CheckpointTracker::new();
+        tracker.window_listed(1, date(4));
+        tracker.window_listed(1, date(8));                  // empty
+        tracker.window_listed(2, date(12));
+        tracker.window_listed(3, date(16));
+        tracker.page_written(1, page_res(7));
+        tracker.page_written(0, page_res(3));
+        assert_eq!(checkpoint(&mut tracker), Some(("2025-01-12".to_string(), 10)));
+        assert_eq!(checkpoint(&mut tracker), None);
+    }
+
+    #[test]
+    fn check_hash_ignores_download_time_and_key_order() {
+        let v1 = json!({"sd_sid": "ISRCTN14757012", "downloaded": "2025-10-18T06:00:00", "acronym": "STYE"});
+        let v2 = json!({"acronym": "STYE", "downloaded": "2025-11-02T07:30:00", "sd_sid": "ISRCTN14757012"});
+        assert_eq!(hash_of_value(v1).unwrap(), hash_of_value(v2).unwrap());
+    }
+
+    #[test]
+    fn check_hash_changes_with_content() {
+        let v1 = json!({"sd_sid": "ISRCTN14757012", "downloaded": "2025-10-18T06:00:00", "acronym": "STYE"});
+        let v2 = json!({"sd_sid": "ISRCTN14757012", "downloaded": "2025-10-18T06:00:00", "acronym": "STYES"});
+        let h1 = hash_of_value(v1).unwrap();
+        assert_ne!(h1, hash_of_value(v2).unwrap());
+        assert_eq!(h1.len(), 64);
+    }
+}
@@ -2,6 +2,7 @@ use crate::data_models::xml_models;
 use crate::data_models::json_models::*; 
 use crate::helpers::string_extensions::*;
 use super::support_fns::{count_option, split_identifier, classify_identifier};
+use super::id_rules::id_rule;
 
 use crate::err::AppError;
 use chrono::Utc;
@@ -50,6 +51,17 @@ pub fn process_study(s: xml_models::FullTrial) -> Result<Study, AppError> {
     // further processing. If the type can be idfentified the value is pushed to the vector with
     // that additional information. Otherwise it is added as a general grant or sponsor's protocol id.
 
+    // Where the type is given, the identifier takes the type id and name of the rule with that
+    // name (always present - see id_rules), or, failing that, is classified like any other.
+
+    let typed_identifier = |rule_name: &str, id: String| match id_rule(rule_name) {
+        Some(rule) => Identifier::new(rule.type_id, rule.type_name.clone(), id),
+        None => {
+            let (type_id, type_string, id_post) = classify_identifier(id);
+            Identifier::new(type_id, type_string, id_post)
+        },
+    };
+
     let er = study.external_refs;
     let sec_ids = er.secondary_number_list.secondary_numbers;
     let mut s_identifiers =  Vec::new();
@@ -70,31 +82,30 @@ pub fn process_study(s: xml_models::FullTrial) -> Result<Study, AppError> {
                     match id_type_string.as_str() {
                         "iras" => {
                             iras_number = id.clone();
-                            s_identifiers.push(Identifier::new(303, "IRAS ID".to_string(), id.to_string()));
+                            s_identifiers.push(typed_identifier("IRAS", id.to_string()));
                         }, 
 
                         "ctis" => {
                             if let Some(id_reg) = id_value.regularise_hyphens(){
-                               if  id_reg.len() == 14 || id_reg.len() == 17 {
-                                    if &id_reg[4..6] == "-5" {
-                                        s_identifiers.push(Identifier::new(135, "EMA CTIS ID".to_string(), id_reg.to_string()));
-                                    } else {
-                                        s_identifiers.push(Identifier::new(123, "EMA Eudract ID".to_string(), id_reg.to_string()));
-                                    }
-                                }
-                                else {
-                                    s_identifiers.push(Identifier::new(179, "Malformed registry Id (CTIS claimed)".to_string(), id_reg.to_string()));
+
+                                // The whole id must be a CTIS or (older) EudraCT number.
+
+                                let registry_id = ["CTIS", "EudraCT"].iter().filter_map(|n| id_rule(n))
+                                    .find_map(|r| r.find_whole(&id_reg).map(|v| Identifier::new(r.type_id, r.type_name.clone(), v)));
+                                match registry_id {
+                                    Some(ident) => s_identifiers.push(ident),
+                                    None => s_identifiers.push(Identifier::new(179, "Malformed registry Id (CTIS claimed)".to_string(), id_reg.to_string())),
                                 }
                             }
                         }, 
 
                         "nct" => {
-                            s_identifiers.push(Identifier::new(120, "NCT ID".to_string(), id.to_string()));
+                            s_identifiers.push(typed_identifier("NCT", id.to_string()));
                         }, 
 
                         "cpms" => {   // May need to have prefix removed
                             if RE_CPMS_NUM.is_match(&id) {   // already a digit string
-                                s_identifiers.push(Identifier::new(304, "CPMS ID".to_string(), id.to_string()));
+                                s_identifiers.push(typed_identifier("CPMS", id.to_string()));
                             }
                             else {
                                 let (type_id, type_string, id_post) = classify_identifier(id.to_string());
@@ -104,7 +115,7 @@ pub fn process_study(s: xml_models::FullTrial) -> Result<Study, AppError> {
 
                         "nihr" => {   
                             if RE_NIHR_NUM.is_match(&id) {   // already a digit string
-                                s_identifiers.push(Identifier::new(416, "NIHR ID".to_string(), id.to_string()));
+                                s_identifiers.push(typed_identifier("NIHR", id.to_string()));
                             }
                             else {// May need to have prefix removed
                                 let (type_id, type_string, id_post) = classify_identifier(id.to_string());
@@ -159,7 +170,7 @@ pub fn process_study(s: xml_models::FullTrial) -> Result<Study, AppError> {
                                     // In some cases this id seems to include the IRAS number - check before adding.
 
                                     let mut add_new = true;
-                                    if type_id == 303 && id_post == iras_number {
+                                    if id_rule("IRAS").is_some_and(|r| r.type_id == type_id) && id_post == iras_number {
                                         add_new = false;
                                     }
                                     if add_new {
@@ -214,6 +225,7 @@ pub fn process_study(s: xml_models::FullTrial) -> Result<Study, AppError> {
         plain_english_summary: plain_summ,
         study_hypothesis: d.study_hypothesis.as_text_opt(),
         primary_outcome: d.primary_outcome.as_text_opt(),
+        secondary_outcome: d.secondary_outcome.as_text_opt(),
         overall_end_date: study.trial_design.overall_end_date.as_date_string_opt(),
         trial_website: d.trial_website.as_text_opt(),
     };
@@ -232,6 +244,45 @@ pub fn process_study(s: xml_models::FullTrial) -> Result<Study, AppError> {
     }
     let primary_outcomes =  count_option(s_primary_outcomes);
 
+    let mut s_secondary_outcomes: Vec<OutcomeMeasure> = Vec::new();
+    if let Some(sos) = d.secondary_outcomes {
+        for om in sos.outcome_measures {
+            s_secondary_outcomes.push(OutcomeMeasure { 
+                variable: om.variable, 
+                method: om.method, 
+                timepoints: om.timepoints,
+            })
+        }
+    }
+    let secondary_outcomes =  count_option(s_secondary_outcomes);
+
+    // Ethics approval, and the committees (if any) that have considered the study.
+
+    let ethics_approval_required = d.ethics_approval_required.as_text_opt();
+    let ethics_approval = d.ethics_approval.as_text_opt();
+    let ethics = if ethics_approval_required.is_some() || ethics_approval.is_some() {
+        Some(Ethics {
+            ethics_approval_required,
+            ethics_approval,
+        })
+    }
+    else {
+        None
+    };
+
+    let mut ethics_comms: Vec<EthicsCommittee> = Vec::new();
+    if let Some(ecl) = d.ethics_committee_list {
+        for ec in ecl.ethics_committees {
+            ethics_comms.push(EthicsCommittee {
+                name: ec.committee_name.as_text_opt(),
+                approval_status: ec.approval_status.as_text_opt(),
+                status_date: ec.status_date.as_date_string_opt(),
+                committee_reference: ec.committee_reference.as_text_opt(),
+            });
+        }
+    }
+    let ethics_committees = count_option(ethics_comms);
+
     // Design block
 
     let ds = study.trial_design;
@@ -470,22 +521,7 @@ pub fn process_study(s: xml_models::FullTrial) -> Result<Study, AppError> {
     let mut s_countries: Vec<String> = Vec::new();
     for c_init in init_countries {
 
-        // Regularise these common alternative spellings / allocations.
-
-        let mut c = c_init.replace("Korea, South", "South Korea");
-        c = c.replace("Congo, Democratic Republic", "Democratic Republic of the Congo");
-        
-        let c_lower = c.to_ascii_lowercase();
-        if c_lower == "england" || c_lower == "scotland" ||
-           c_lower == "wales" || c_lower == "northern ireland"
-        {
-                c = "United Kingdom".to_string();
-        }
-
-        if c_lower == "united states of america"
-        {
-                c = "United States".to_string();
-        }
+        let c = regularise_country(&c_init);
 
         // Check for duplicates before adding, especially after changes above.
 
@@ -509,6 +545,22 @@ pub fn process_study(s: xml_models::FullTrial) -> Result<Study, AppError> {
 
     let countries = count_option(s_countries);
 
+    // Trial centres (sites), with their country names regularised in the same way.
+
+    let mut s_centres: Vec<StudyCentre> = Vec::new();
+    if let Some(cl) = p.centre_list {
+        for c in cl.centres {
+            s_centres.push(StudyCentre {
+                name: c.name.as_text_opt(),
+                address: c.address.as_text_opt(),
+                city: c.city.as_text_opt(),
+                state: c.state.as_text_opt(),
+                country: c.country.as_text_opt().map(|s| regularise_country(&s)),
+            });
+        }
+    }
+    let centres = count_option(s_centres);
+
     // Results
 
     let r = study.results;
@@ -657,6 +709,7 @@ pub fn process_study(s: xml_models::FullTrial) -> Result<Study, AppError> {
 
     let json_study = Study { 
         sd_sid, 
+        schema_version: SCHEMA_VERSION,
         downloaded,
         registration, 
         public_title,
@@ -665,6 +718,7 @@ pub fn process_study(s: xml_models::FullTrial) -> Result<Study, AppError> {
         identifiers,
         summary,
         primary_outcomes,
+        secondary_outcomes,
         design,
         trial_types,
         conditions,
@@ -676,6 +730,9 @@ pub fn process_study(s: xml_models::FullTrial) -> Result<Study, AppError> {
         participants,
         recruitment,
         countries,
+        centres,
+        ethics,
+        ethics_committees,
         data_policies,
         results,
         links,
@@ -688,63 +745,31 @@ pub fn process_study(s: xml_models::FullTrial) -> Result<Study, AppError> {
 }
 
 
-// PREVIOUSLY INCLUDED - BEFORE DOWNLOAD WAS SIMPLIFIED
+fn regularise_country(country: &str) -> String {
 
-/* 
-    let mut s_secondary_outcomes: Vec<OutcomeMeasure> = Vec::new();
-    if d.secondary_outcomes.outcome_measures.len() > 0 {
-        for om in d.secondary_outcomes.outcome_measures {
-            s_secondary_outcomes.push(OutcomeMeasure { 
-                variable: om.variable, 
-                method: om.method, 
-                timepoints: om.timepoints,
-            })
-        }
-    } 
-    let secondary_outcomes =  count_option(s_secondary_outcomes);
-*/
+    // Regularise these common alternative spellings / allocations.
 
-    // Ethics Committee data
+    let mut c = country.replace("Korea, South", "South Korea");
+    c = c.replace("Congo, Democratic Republic", "Democratic Republic of the Congo");
 
-/* 
-    let ethics = Ethics {
-        ethics_approval_required: d.ethics_approval_required.as_text_opt(),
-        ethics_approval: d.ethics_approval.as_text_opt(),
-    };
-
-    let mut ethics_comms = Vec::new();
-    if d.ethics_committee_list.ethics_committees.len() > 0 {
-        for ec in d.ethics_committee_list.ethics_committees {
-            
-            let committee = EthicsCommittee {
-                name: ec.committee_name.as_text_opt(),
-                approval_status: ec.approval_status.as_text_opt(),
-                status_date: ec.status_date.as_date_opt(),
-                committee_reference: ec.committee_reference.as_text_opt(),
-            };
-            ethics_comms.push(committee);
-        }
+    let c_lower = c.to_ascii_lowercase();
+    if c_lower == "england" || c_lower == "scotland" ||
+       c_lower == "wales" || c_lower == "northern ireland"
+    {
+            c = "United Kingdom".to_string();
     }
-    let ethics_committees = count_option(ethics_comms);
-*/
 
-    
-/* 
-    let mut s_centres: Vec<StudyCentre> = Vec::new();
-    if p.centre_list.centres.len() > 0 {
-        for c in p.centre_list.centres {
-            s_centres.push(StudyCentre {
-                name: c.name.as_text_opt(),
-                address: c.address.as_text_opt(),
-                city: c.city.as_text_opt(),
-                state: c.state.as_text_opt(),
-                country: c.country.as_text_opt(),
-            });
-        }  
+    if c_lower == "united states of america"
+    {
+            c = "United States".to_string();
     }
-    let centres = count_option(s_centres);
-*/
+    c
+}
+
 
+// PREVIOUSLY INCLUDED - BEFORE DOWNLOAD WAS SIMPLIFIED
+
+    
 
 /* 
     let mut t_settings: Vec<String> = Vec::new();
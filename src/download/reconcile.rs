@@ -0,0 +1,156 @@
+use super::{get_study_count, api_client::ApiClient};
+use super::windows::*;
+use crate::data_models::xml_models::AllTrialIds;
+use crate::recording::processes::MonitorRepo;
+use crate::base_types::{InitParams, DownloadResult};
+use crate::AppError;
+use chrono::{Days, NaiveDate, Utc};
+use log::{info, warn};
+use quick_xml::de;
+use std::collections::HashSet;
+
+// A reconciliation (--reconcile in the CLI) lists the ids of all the studies currently in
+// ISRCTN, by walking through the whole period of the registry in 'dateApplied' windows, in
+// the same way as a download, but reading only the ISRCTN id of each study returned.
+// Studies in mn.source_data that are no longer in the registry (i.e. have been withdrawn or
+// deleted) are then flagged as 'missing', with the date that was found, and any that were
+// flagged earlier but have reappeared are flagged as 'current' again. No json files are written.
+// Because an incomplete listing would wrongly flag studies, nothing is flagged unless the
+// listing includes at least as many ids as the registry's total (from a single count query,
+// independent of the windows), and the number to be flagged is a small fraction of the total.
+
+const ISRCTN_START_YEAR: i32 = 2000;
+const ISRCTN_START_MONTH: u32 = 4;
+const MAX_MISSING_FRACTION: f64 = 0.02;
+
+
+pub async fn reconcile_ids(params: &InitParams, monitor: &MonitorRepo) -> Result<DownloadResult, AppError> {
+
+    let api = ApiClient::new(&params.api_pars)?;
+    let start_date = NaiveDate::from_ymd_opt(ISRCTN_START_YEAR, ISRCTN_START_MONTH, 1).unwrap();
+    let end_date = Utc::now().date_naive().checked_add_days(Days::new(1)).unwrap();   // includes today
+
+    let registry_total = get_registry_total(params, &api, end_date).await?;
+    let (registry_ids, num_counted) = list_registry_ids(params, &api, start_date, end_date).await?;
+    info!("{} study ids listed from ISRCTN, {} in the registry", registry_ids.len(), registry_total);
+
+    let ids: Vec<String> = registry_ids.into_iter().collect();
+    let (num_stored, num_to_flag) = monitor.count_studies_to_flag(&ids).await?;
+    match check_listing(ids.len() as i64, registry_total as i64, num_stored, num_to_flag) {
+        Ok(()) => {
+            let (num_missing, num_restored) = monitor.reconcile_registry_ids(&ids).await?;
+            info!("{} studies newly flagged as missing from ISRCTN, {} found again", num_missing, num_restored);
+        },
+        Err(reason) => warn!("{} - no studies have been flagged", reason),
+    }
+
+    Ok(DownloadResult {
+        num_checked: num_counted,
+        num_downloaded: 0,
+        num_added: 0,
+        num_unchanged: 0,
+    })
+}
+
+
+async fn get_registry_total(params: &InitParams, api: &ApiClient, end_date: NaiveDate) -> Result<i32, AppError> {
+
+    // The whole registry is counted in one request, with a range that starts well
+    // before the registry did, so that the total does not depend on the windows.
+
+    let from = NaiveDate::from_ymd_opt(1900, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let url = format!("{}{}", params.api_base_url, range_query("dateApplied", from, end_date.and_hms_opt(0, 0, 0).unwrap(), 1));
+    get_study_count(api, &url).await
+}
+
+
+fn check_listing(num_listed: i64, registry_total: i64, num_stored: i64, num_to_flag: i64) -> Result<(), String> {
+
+    // num_to_flag is the number of studies in mn.source_data, not already flagged as
+    // missing, that are not in the listing and so would now be flagged.
+
+    if num_listed == 0 || num_listed < registry_total {
+        return Err(format!("The list of ISRCTN ids appears to be incomplete ({} listed, {} in the registry)", num_listed, registry_total));
+    }
+    if num_to_flag as f64 > MAX_MISSING_FRACTION * num_stored as f64 {
+        return Err(format!("{} of the {} studies in mn.source_data would be flagged as missing, more than the {}% allowed",
+                            num_to_flag, num_stored, MAX_MISSING_FRACTION * 100.0));
+    }
+    Ok(())
+}
+
+
+async fn list_registry_ids(params: &InitParams, api: &ApiClient, start_date: NaiveDate,
+                           end_date: NaiveDate) -> Result<(HashSet<String>, i32), AppError> {
+
+    // Windows are split and lengthened exactly as in a download, so that every
+    // range can be requested in a single call.
+
+    let range_parameter = "dateApplied";
+    let page_size = params.api_pars.page_size;
+    let min_span = min_span(range_parameter);
+    let mut registry_ids = HashSet::new();
+    let mut num_counted = 0;
+    let mut sd = start_date;
+    let mut window_days = BASE_WINDOW_DAYS;
+
+    while sd < end_date {
+
+        let ed = sd.checked_add_days(Days::new(window_days)).unwrap().min(end_date);
+        let mut outcome = WindowOutcome::Empty;
+        let mut ranges = vec![(sd.and_hms_opt(0, 0, 0).unwrap(), ed.and_hms_opt(0, 0, 0).unwrap())];
+
+        while let Some((from, to)) = ranges.pop() {
+
+            let url = format!("{}{}", params.api_base_url, range_query(range_parameter, from, to, 1));
+            let record_num = get_study_count(api, &url).await?;
+            if record_num == 0 {
+                continue;
+            }
+
+            if record_num > page_size
+                && let Some(mid) = split_point(from, to, min_span) {
+                outcome = WindowOutcome::Split;
+                ranges.push((mid, to));
+                ranges.push((from, mid));
+            }
+            else {
+                if outcome == WindowOutcome::Empty {
+                    outcome = WindowOutcome::Single;
+                }
+                let url = format!("{}{}", params.api_base_url, range_query(range_parameter, from, to, record_num));
+                let xml_content = api.get_text(&url).await?;
+                let trial_ids: AllTrialIds = de::from_str(&xml_content)
+                    .map_err(|e| AppError::QuickXMLError(url.clone(), e))?;
+                num_counted += record_num;
+                registry_ids.extend(trial_ids.full_trials.into_iter().map(|t| format!("ISRCTN{}", t.trial.isrctn.value)));
+            }
+        }
+
+        window_days = next_window_days(window_days, outcome);
+        sd = ed;
+    }
+
+    Ok((registry_ids, num_counted))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_incomplete_listing_refused() {
+        assert!(check_listing(0, 0, 100, 100).unwrap_err().starts_with("The list of ISRCTN ids appears to be incomplete"));
+        assert!(check_listing(27_990, 28_000, 28_000, 10).is_err());
+        assert!(check_listing(28_000, 28_000, 28_000, 10).is_ok());
+        assert!(check_listing(28_010, 28_000, 28_000, 10).is_ok());
+    }
+
+    #[test]
+    fn check_large_number_missing_refused() {
+        assert!(check_listing(28_000, 28_000, 28_000, 560).is_ok());
+        assert_eq!(check_listing(28_000, 28_000, 28_000, 561).unwrap_err(),
+                   "561 of the 28000 studies in mn.source_data would be flagged as missing, more than the 2% allowed");
+    }
+}
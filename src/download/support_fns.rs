@@ -1,5 +1,6 @@
 use std::sync::LazyLock;
 use regex::Regex;
+use super::id_rules::id_rules;
 
 
 pub fn count_option<T>(v: Vec<T>) -> Option<Vec<T>> {
@@ -91,82 +92,16 @@ pub fn split_identifier(id: &String) -> Vec<String> {
 
 pub fn classify_identifier(identifier_value: String) -> (i32, String, String) {
 
-    // Attempts to identify the type of some of the more common / distinctive (non trial registry) identifiers
+    // Attempts to identify the type of an identifier, using the rules listed in
+    // rules/identifier_rules.toml, the first rule that matches giving the type.
+    // If none match the original value is returned, with a type string of '???'.
 
-    static RE_IRAS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"IRAS:? ?\d{6,7}").unwrap());
-    static RE_CPMS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"CPMS:? ?\d{5}").unwrap());
-    static RE_NIHR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"NIHR:? ?\d{6}").unwrap());
-    static RE_HTA: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"HTA \d{2}/\d{2,3}/\d{2,3}").unwrap());
-    static RE_NTR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"NTR:? ?\d{2,6}").unwrap());
-    static RE_CCMO: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"NL\d{5}.\d{3}.\d{2}").unwrap());
-    static RE_CIV: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"CIV-\d{2}-\d{2}-\d{6}").unwrap());
-    static RE_ANSM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\d{4}-A\d{5}-\d{2}").unwrap());
-    
-    match RE_IRAS.captures(&identifier_value) {
-        Some(s) => {
-            let new_value = s[0].to_string().replace("IRAS", "").replace(":", "").trim().to_string();
-            (303, "IRAS ID".to_string(), new_value)
-        },
-        None => {
-            match RE_CPMS.captures(&identifier_value) {
-                Some(s) => {
-                    let new_value = s[0].to_string().replace("CPMS", "").replace(":", "").trim().to_string();
-                    (304, "CPMS ID".to_string(), new_value)
-                },
-                None => {
-                    match RE_NIHR.captures(&identifier_value) {
-                        Some(s) => {
-                            let new_value = s[0].to_string().replace("NIHR", "").replace(":", "").trim().to_string();
-                            (416, "NIHR ID".to_string(), new_value)
-                        },
-                        None => {
-                            match RE_HTA.captures(&identifier_value) {
-                                Some(s) => {
-                                    let new_value = s[0].to_string();
-                                    (417, "HTA ID".to_string(), new_value)
-                                },
-                                None => {
-                                    match RE_NTR.captures(&identifier_value) {
-                                        Some(s) => {
-                                            let new_value = s[0].to_string();
-                                            (181, "Obsolete NTR ID".to_string(), new_value)
-                                        },
-                                        None =>  {
-                                            match RE_CCMO.captures(&identifier_value) {
-                                                Some(s) => {
-                                                    let new_value = s[0].to_string();
-                                                    (801, "CCMO ethics ID".to_string(), new_value)
-                                                },
-                                                None => {
-                                                    match RE_CIV.captures(&identifier_value) {
-                                                        Some(s) => {
-                                                            let new_value = s[0].to_string();
-                                                            (186, "Eudamed CIV ID".to_string(), new_value)
-                                                        },
-                                                        None => {
-                                                            match RE_ANSM.captures(&identifier_value) {
-                                                                Some(s) => {
-                                                                    let new_value = s[0].to_string();
-                                                                    (301, "ANSM (ID-RCB number)".to_string(), new_value)
-                                                                },
-                                                                None => (502, "???".to_string(), identifier_value)
-                                                            }
-                                                        },  
-                                                    }
-                                                },
-                                            }  
-                                        },  
-                                    }
-                                },  
-                            }
-                        },  
-                    }
-                },  
-            }
-        },  
+    for rule in id_rules() {
+        if let Some(new_value) = rule.find(&identifier_value) {
+            return (rule.type_id, rule.type_name.clone(), new_value)
+        }
     }
-   
-
+    (502, "???".to_string(), identifier_value)
 }
 
 
@@ -0,0 +1,124 @@
+use chrono::{NaiveDateTime, TimeDelta};
+
+// Helper functions for the date / time windows used when requesting
+// records from the API. Windows are always half open, i.e. [from, to).
+
+pub const BASE_WINDOW_DAYS: u64 = 4;
+pub const MAX_WINDOW_DAYS: u64 = 64;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum WindowOutcome {
+    Empty,      // no records found in the window
+    Single,     // all records obtained in a single request
+    Split,      // window had to be split into smaller ranges
+}
+
+
+pub fn next_window_days(current: u64, outcome: WindowOutcome) -> u64 {
+
+    // Empty windows are followed by larger ones (so that quiet periods cost fewer
+    // count calls), windows that had to be split by smaller ones.
+
+    match outcome {
+        WindowOutcome::Empty => (current * 2).min(MAX_WINDOW_DAYS),
+        WindowOutcome::Single => current,
+        WindowOutcome::Split => (current / 2).max(1),
+    }
+}
+
+
+pub fn min_span(range_parameter: &str) -> TimeDelta {
+
+    // Only 'lastEdited' is split into sub-day ranges. 'dateApplied'
+    // is treated as a date, so one day is the smallest range used.
+
+    if range_parameter == "lastEdited" {
+        TimeDelta::minutes(1)
+    }
+    else {
+        TimeDelta::days(1)
+    }
+}
+
+
+pub fn split_point(from: NaiveDateTime, to: NaiveDateTime, min_span: TimeDelta) -> Option<NaiveDateTime> {
+
+    // Returns the mid point of the range, or None if the range cannot be split
+    // without creating a range smaller than the minimum span. Mid points are
+    // kept to whole days if the minimum span is a day, and whole seconds otherwise.
+
+    let span = to - from;
+    if span < min_span * 2 {
+        return None;
+    }
+    if min_span >= TimeDelta::days(1) {
+        Some(from + TimeDelta::days(span.num_days() / 2))
+    }
+    else {
+        Some(from + TimeDelta::seconds(span.num_seconds() / 2))
+    }
+}
+
+
+pub fn range_query(range_parameter: &str, from: NaiveDateTime, to: NaiveDateTime, limit: i32) -> String {
+    format!("{}%20GE%20{}%20AND%20{}%20LT%20{}%20&limit={}", range_parameter, from.format("%Y-%m-%dT%H:%M:%S"),
+                        range_parameter, to.format("%Y-%m-%dT%H:%M:%S"), limit)
+}
+
+
+pub fn page_name(range_parameter: &str, from: NaiveDateTime, to: NaiveDateTime) -> String {
+    format!("{}_{}_{}", range_parameter, from.format("%Y-%m-%dT%H%M%S"), to.format("%Y-%m-%dT%H%M%S"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn dt(y: i32, m: u32, d: u32, h: u32, mi: u32, s: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap().and_hms_opt(h, mi, s).unwrap()
+    }
+
+    #[test]
+    fn check_window_grows_when_empty_up_to_max() {
+        assert_eq!(next_window_days(4, WindowOutcome::Empty), 8);
+        assert_eq!(next_window_days(64, WindowOutcome::Empty), 64);
+        assert_eq!(next_window_days(48, WindowOutcome::Empty), 64);
+    }
+
+    #[test]
+    fn check_window_shrinks_when_split_down_to_one() {
+        assert_eq!(next_window_days(8, WindowOutcome::Split), 4);
+        assert_eq!(next_window_days(1, WindowOutcome::Split), 1);
+        assert_eq!(next_window_days(4, WindowOutcome::Single), 4);
+    }
+
+    #[test]
+    fn check_split_point_for_days() {
+        let md = min_span("dateApplied");
+        assert_eq!(split_point(dt(2024, 3, 1, 0, 0, 0), dt(2024, 3, 5, 0, 0, 0), md), Some(dt(2024, 3, 3, 0, 0, 0)));
+        assert_eq!(split_point(dt(2024, 3, 1, 0, 0, 0), dt(2024, 3, 4, 0, 0, 0), md), Some(dt(2024, 3, 2, 0, 0, 0)));
+        assert_eq!(split_point(dt(2024, 3, 1, 0, 0, 0), dt(2024, 3, 2, 0, 0, 0), md), None);
+    }
+
+    #[test]
+    fn check_split_point_for_sub_day_ranges() {
+        let md = min_span("lastEdited");
+        assert_eq!(split_point(dt(2024, 3, 1, 0, 0, 0), dt(2024, 3, 2, 0, 0, 0), md), Some(dt(2024, 3, 1, 12, 0, 0)));
+        assert_eq!(split_point(dt(2024, 3, 1, 9, 0, 0), dt(2024, 3, 1, 9, 3, 0), md), Some(dt(2024, 3, 1, 9, 1, 30)));
+        assert_eq!(split_point(dt(2024, 3, 1, 9, 0, 0), dt(2024, 3, 1, 9, 1, 59), md), None);
+    }
+
+    #[test]
+    fn check_range_query_format() {
+        let q = range_query("lastEdited", dt(2024, 3, 1, 0, 0, 0), dt(2024, 3, 1, 12, 30, 0), 100);
+        assert_eq!(q, "lastEdited%20GE%202024-03-01T00:00:00%20AND%20lastEdited%20LT%202024-03-01T12:30:00%20&limit=100");
+    }
+
+    #[test]
+    fn check_page_name_format() {
+        let p = page_name("dateApplied", dt(2024, 3, 1, 0, 0, 0), dt(2024, 3, 5, 0, 0, 0));
+        assert_eq!(p, "dateApplied_2024-03-01T000000_2024-03-05T000000");
+    }
+}
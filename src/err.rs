@@ -53,6 +53,18 @@ pub enum AppError {
 
     #[error("Deserialisation error fetching from {0:?}")]
     QuickXMLError(String, #[source] quick_xml::DeError),
+
+    #[error("Error when running a concurrent task: {0:?}")]
+    TaskError(#[from] tokio::task::JoinError),
+
+    #[error("Unsupported json schema version {0} in file {1:?}")]
+    SchemaVersionError(u32, std::path::PathBuf),
+
+    #[error("Unable to swap in the new ad tables: {0:?} {1:?}")]
+    SchemaSwapError(String, String),
+
+    #[error("The live ad tables differ in structure from the new ones: {0:?} {1:?}")]
+    TableStructureError(String, String),
 }
 
 
@@ -97,6 +109,15 @@ pub fn report_error(e: AppError) -> () {
         AppError::ReqwestError(url, e) => print_error (url, e.to_string(), "REQWEST ERROR"),
 
         AppError::QuickXMLError(url, e) => print_error (url, e.to_string(), "QUICK XML DESER ERROR"),
+
+        AppError::TaskError(e) => print_simple_error (e.to_string(), "CONCURRENT TASK ERROR"),
+
+        AppError::SchemaVersionError(v, p) => print_error (format!("Json file has schema version {}, which is newer than this program supports", v),
+                  "Path was: ".to_string() + p.to_str().unwrap(), "JSON SCHEMA VERSION ERROR"),
+
+        AppError::SchemaSwapError(d, s) => print_error (d, s, "SCHEMA SWAP ERROR"),
+
+        AppError::TableStructureError(d, s) => print_error (d, s, "TABLE STRUCTURE ERROR"),
     }
 }
 
@@ -0,0 +1,443 @@
+use super::iec_structs::*;
+use regex::Regex;
+use std::sync::LazyLock;
+
+// Extracts common structured constraints from the text of the criteria lines, to support
+// matching people to trials by eligibility. The constraints recognised are age limits and
+// ranges, pregnancy and breastfeeding, BMI thresholds, thresholds for eGFR and other common
+// laboratory values, and requirements for (or exclusions of) a diagnosis. Each is linked
+// to the line it was found in by the line's seq_num and type. Whether the constraint is
+// a requirement or an exclusion is given by the type of the line, unless the constraint
+// is negated - e.g. 'not pregnant' in an inclusion criterion, or 'no previous diagnosis of'
+// - in which case the sense is reversed. Only the wording most often used in the ISRCTN
+// records is recognised, so the extraction is deliberately cautious - a constraint that
+// is missed is better than one that is wrong.
+
+// Numeric limits are expressed as a comparator ('>', '>=', '<', '<=', '=' or 'between'),
+// with the value in value_low for a lower limit and in value_high for an upper limit.
+
+#[derive(Debug, Clone, PartialEq)]
+struct Limit {
+    comparator: String,
+    value_low: Option<f64>,
+    value_high: Option<f64>,
+    units: Option<String>,
+}
+
+const NUMBER: &str = r"(\d+(?:\.\d+)?)";
+
+const UNITS: &str = r"(%|years?|yrs?|months?|weeks?|days?|kg/m2|kg/m²|ml/min/1\.73\s?m2|ml/min/1\.73\s?m²|ml/min|mmol/mol|mmol/l|µmol/l|umol/l|mg/dl|mg/l|g/dl|g/l|x\s?10\^?9/l|x\s?uln|times the upper limit of normal)";
+
+const COMPARATORS: &str = r"(greater than or equal to|less than or equal to|greater than|more than|higher than|less than|lower than|no more than|not more than|no less than|at least|at most|exceeding|above|over|below|under|>=|<=|=>|=<|≥|≤|>|<|=)";
+
+// Text allowed between a term and its limit, e.g. the '(kg/m2) of' in 'BMI (kg/m2) of 30 or more'.
+
+const LIMIT_PREFIX: &str = r"^\s*(?:\([^)]{0,20}\))?[\s:,]*(?:(?:of|level|levels|value|is|was|must be|should be)\s+)*";
+
+const AGE_UNITS: [&str; 4] = ["year", "month", "week", "day"];
+
+// A term is negated if one of these words occurs in the few words before it, within the same
+// clause (as in NegEx). The clause ends at any punctuation other than a comma, or at 'but'
+// etc., so that, e.g., in 'No contraindications; pregnant' the pregnancy is not negated,
+// whereas in 'Not pregnant, breastfeeding or planning a pregnancy' all three terms are.
+
+const NEGATION: &str = r"(?i)\b(?:not|no|non|without|never|neither|nor|cannot|free\s+(?:of|from)|absence\s+of)\b|n't\b";
+
+const CLAUSE_END: &str = r"(?i)[;:.()\[\]]|\b(?:but|however|although|except|apart from|unless)\b";
+
+const NEGATION_WINDOW: usize = 6;     // the number of words before a term that are examined
+
+// Common laboratory tests, with the name used for each in the constraints.
+// Where one name includes another (e.g. 'glycated haemoglobin' and 'haemoglobin')
+// the longer is listed first, and text matched by one test is not used for another.
+
+const LAB_TESTS: [(&str, &str); 11] = [
+    ("eGFR", r"\be-?GFR\b|\bestimated glomerular filtration rate\b"),
+    ("creatinine clearance", r"\bcreatinine clearance\b|\bCrCl\b"),
+    ("creatinine", r"\b(?:serum )?creatinine\b"),
+    ("HbA1c", r"\bHbA1c\b|\bglycated ha?emoglobin\b"),
+    ("haemoglobin", r"\bha?emoglobin\b|\bHb\b"),
+    ("platelets", r"\bplatelets?(?: count)?\b"),
+    ("neutrophils", r"\b(?:absolute )?neutrophils?(?: count)?\b"),
+    ("ALT", r"\bALT\b|\balanine aminotransferase\b"),
+    ("AST", r"\bAST\b|\baspartate aminotransferase\b"),
+    ("bilirubin", r"\b(?:total )?bilirubin\b"),
+    ("potassium", r"\b(?:serum )?potassium\b"),
+];
+
+
+pub fn extract_constraints(lines: &[IECLine]) -> Vec<IECConstraint> {
+
+    let mut constraints = Vec::new();
+    for line in lines {
+        let mut add = |constraint_type: &str, parameter: &str, limit: Option<Limit>, negated: bool| {
+            let limit = limit.unwrap_or(Limit { comparator: "".to_string(), value_low: None, value_high: None, units: None });
+            constraints.push(IECConstraint {
+                seq_num: line.seq_num,
+                ie_type_id: line.type_id,
+                constraint_type: constraint_type.to_string(),
+                parameter: parameter.to_string(),
+                comparator: if limit.comparator.is_empty() {None} else {Some(limit.comparator)},
+                value_low: limit.value_low,
+                value_high: limit.value_high,
+                units: limit.units,
+                negated,
+            });
+        };
+
+        // Each finder returns the position of the term found, to check for a negation.
+
+        let text = &line.text;
+        if let Some((pos, limit)) = find_age_limit(text) {
+            add("age", "age", Some(limit), is_negated(text, pos));
+        }
+        for (pos, term) in find_pregnancy_terms(text) {
+            add("pregnancy", term, None, is_negated(text, pos));
+        }
+        if let Some((pos, limit)) = find_bmi_limit(text) {
+            add("bmi", "BMI", Some(limit), is_negated(text, pos));
+        }
+        for (pos, test, limit) in find_lab_limits(text) {
+            add("lab_value", test, Some(limit), is_negated(text, pos));
+        }
+        if let Some((pos, diagnosis)) = find_diagnosis(text) {
+            add("diagnosis", &diagnosis, None, is_negated(text, pos));
+        }
+    }
+    constraints
+}
+
+
+fn is_negated(text: &str, term_start: usize) -> bool {
+
+    static RE_NEGATION: LazyLock<Regex> = LazyLock::new(|| Regex::new(NEGATION).unwrap());
+    static RE_CLAUSE_END: LazyLock<Regex> = LazyLock::new(|| Regex::new(CLAUSE_END).unwrap());
+
+    let before = &text[..term_start];
+    let clause_start = RE_CLAUSE_END.find_iter(before).last().map(|m| m.end()).unwrap_or(0);
+    let words: Vec<&str> = before[clause_start..].split_whitespace().collect();
+    let window = words[words.len().saturating_sub(NEGATION_WINDOW)..].join(" ");
+    RE_NEGATION.is_match(&window)
+}
+
+
+fn parse_limit(s: &str) -> Option<Limit> {
+
+    // Examines the text immediately following a term (such as 'aged' or 'BMI')
+    // for a range, a value followed by e.g. 'or over', or a comparator and value.
+
+    static RE_RANGE: LazyLock<Regex> = LazyLock::new(|| Regex::new(&format!(
+        r"(?i){LIMIT_PREFIX}(?:between|from)?\s*{NUMBER}\s*{UNITS}?\s*(?:and|to|-|–)\s*{NUMBER}\s*{UNITS}?")).unwrap());
+    static RE_SUFFIX: LazyLock<Regex> = LazyLock::new(|| Regex::new(&format!(
+        r"(?i){LIMIT_PREFIX}{NUMBER}\s*{UNITS}?\s*(?:of age\s+|old\s+)?(?:or|and)\s+(over|above|more|greater|higher|older|less|lower|below|under|younger)\b")).unwrap());
+    static RE_COMPARATOR: LazyLock<Regex> = LazyLock::new(|| Regex::new(&format!(
+        r"(?i){LIMIT_PREFIX}{COMPARATORS}\s*{NUMBER}\s*{UNITS}?")).unwrap());
+
+    let value = |m: Option<regex::Match>| m.and_then(|m| m.as_str().parse::<f64>().ok());
+    let units = |m: Option<regex::Match>| m.map(|m| m.as_str().to_lowercase());
+
+    if let Some(c) = RE_RANGE.captures(s) {
+        return Some(Limit {
+            comparator: "between".to_string(),
+            value_low: value(c.get(1)),
+            value_high: value(c.get(3)),
+            units: units(c.get(4)).or(units(c.get(2))),
+        });
+    }
+
+    if let Some(c) = RE_SUFFIX.captures(s) {
+        let is_lower_limit = matches!(c[3].to_lowercase().as_str(), "over" | "above" | "more" | "greater" | "higher" | "older");
+        return Some(limit_from(if is_lower_limit {">="} else {"<="}, value(c.get(1)), units(c.get(2))));
+    }
+
+    if let Some(c) = RE_COMPARATOR.captures(s) {
+        let comparator = match c[1].to_lowercase().as_str() {
+            ">=" | "=>" | "≥" | "at least" | "no less than" | "greater than or equal to" => ">=",
+            "<=" | "=<" | "≤" | "at most" | "no more than" | "not more than" | "less than or equal to" => "<=",
+            "<" | "less than" | "lower than" | "below" | "under" => "<",
+            "=" => "=",
+            _ => ">",
+        };
+        return Some(limit_from(comparator, value(c.get(2)), units(c.get(3))));
+    }
+    None
+}
+
+
+fn limit_from(comparator: &str, value: Option<f64>, units: Option<String>) -> Limit {
+    let is_lower_limit = comparator.starts_with('>') || comparator == "=";
+    Limit {
+        comparator: comparator.to_string(),
+        value_low: if is_lower_limit {value} else {None},
+        value_high: if comparator == "=" || !is_lower_limit {value} else {None},
+        units,
+    }
+}
+
+
+fn find_age_limit(text: &str) -> Option<(usize, Limit)> {
+
+    // Either following 'aged' or 'age' (e.g. 'aged 18 to 65 years', 'age ≥ 18 years'),
+    // or in phrases such as 'over the age of 16' or '18 years or older'.
+
+    static RE_AGE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\bage(?:d|\s+range)?\b").unwrap());
+    static RE_AGE_OF: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\b(over|above|older than|under|below|younger than)\s+(?:the\s+)?age\s+of\s+(\d+)").unwrap());
+    static RE_YEARS: LazyLock<Regex> = LazyLock::new(|| Regex::new(
+        r"(?i)\b(\d+)\s*(years?|yrs?)\s*(?:of age\s+|old\s+)?(?:or|and)\s+(over|above|older|more)\b").unwrap());
+
+    let is_age_limit = |limit: &Limit| limit.units.as_deref()
+        .is_none_or(|u| AGE_UNITS.iter().any(|a| u.starts_with(a)) || u.starts_with("yr"));
+
+    for m in RE_AGE.find_iter(text) {
+        if let Some(mut limit) = parse_limit(&text[m.end()..])
+            && is_age_limit(&limit) {
+            limit.units = Some(age_units(limit.units));
+            return Some((m.start(), limit));
+        }
+    }
+
+    if let Some(c) = RE_AGE_OF.captures(text) {
+        let is_lower_limit = matches!(c[1].to_lowercase().as_str(), "over" | "above" | "older than");
+        return Some((c.get(0).unwrap().start(), limit_from(if is_lower_limit {">"} else {"<"}, c[2].parse().ok(), Some("years".to_string()))));
+    }
+
+    if let Some(c) = RE_YEARS.captures(text) {
+        return Some((c.get(0).unwrap().start(), limit_from(">=", c[1].parse().ok(), Some("years".to_string()))));
+    }
+    None
+}
+
+
+fn age_units(units: Option<String>) -> String {
+
+    // Age units are given in the plural, with years assumed if none are given.
+
+    match units.as_deref() {
+        Some(u) if u.starts_with("month") => "months".to_string(),
+        Some(u) if u.starts_with("week") => "weeks".to_string(),
+        Some(u) if u.starts_with("day") => "days".to_string(),
+        _ => "years".to_string(),
+    }
+}
+
+
+fn find_pregnancy_terms(text: &str) -> Vec<(usize, &'static str)> {
+
+    // A mention of a pregnancy test (e.g. 'a negative pregnancy test') is not itself a constraint.
+    // The position returned is that of the first mention of each term.
+
+    static RE_PREGNANCY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\bpregnan(?:t|cy|cies)\b").unwrap());
+    static RE_BREASTFEEDING: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\b(?:breast[- ]?feeding|lactating|lactation)\b").unwrap());
+
+    let mut terms = Vec::new();
+    if let Some(m) = RE_PREGNANCY.find_iter(text).find(|m| !text[m.end()..].to_lowercase().trim_start().starts_with("test")) {
+        terms.push((m.start(), "pregnancy"));
+    }
+    if let Some(m) = RE_BREASTFEEDING.find(text) {
+        terms.push((m.start(), "breastfeeding"));
+    }
+    terms
+}
+
+
+fn find_bmi_limit(text: &str) -> Option<(usize, Limit)> {
+
+    static RE_BMI: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\b(?:BMI|body mass index)\b").unwrap());
+
+    RE_BMI.find_iter(text).find_map(|m| parse_limit(&text[m.end()..]).map(|l| (m.start(), l)))
+        .map(|(pos, mut limit)| {
+            limit.units = Some("kg/m2".to_string());
+            (pos, limit)
+        })
+}
+
+
+fn find_lab_limits(text: &str) -> Vec<(usize, &'static str, Limit)> {
+
+    // A limit following a list of tests applies to each, e.g. 'ALT or AST > 3 x ULN' gives
+    // limits for both ALT and AST. The mentions of the tests are therefore examined in
+    // reverse order, each taking the limit that follows it or, if there is none and only
+    // 'or', 'and', '/' or a comma separate it from the next mention, that mention's limit.
+
+    static RE_LABS: LazyLock<Vec<(&str, Regex)>> = LazyLock::new(|| LAB_TESTS.iter()
+        .map(|(name, pattern)| (*name, Regex::new(&format!("(?i){}", pattern)).unwrap()))
+        .collect());
+    static RE_LIST_JOIN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)^[\s,]*(?:or|and|and/or|/)?[\s,]*$").unwrap());
+
+    let mut mentions: Vec<(usize, usize, &'static str)> = Vec::new();
+    for (name, re) in RE_LABS.iter() {
+        for m in re.find_iter(text) {
+            if !mentions.iter().any(|(s, e, _)| m.start() < *e && m.end() > *s) {
+                mentions.push((m.start(), m.end(), *name));
+            }
+        }
+    }
+    mentions.sort();
+
+    let mut mention_limits: Vec<Option<Limit>> = vec![None; mentions.len()];
+    for i in (0..mentions.len()).rev() {
+        let (_, end, _) = mentions[i];
+        mention_limits[i] = parse_limit(&text[end..]).or_else(|| {
+            mentions.get(i + 1)
+                .filter(|(next_start, _, _)| RE_LIST_JOIN.is_match(&text[end..*next_start]))
+                .and_then(|_| mention_limits[i + 1].clone())
+        });
+    }
+
+    // Each test is listed once, with the first limit found for it, in the order of LAB_TESTS.
+
+    let mut limits = Vec::new();
+    for (name, _) in LAB_TESTS.iter() {
+        if let Some((i, _)) = mentions.iter().enumerate()
+            .find(|(i, (_, _, n))| n == name && mention_limits[*i].is_some()) {
+            limits.push((mentions[i].0, *name, mention_limits[i].clone().unwrap()));
+        }
+    }
+    limits
+}
+
+
+fn find_diagnosis(text: &str) -> Option<(usize, String)> {
+
+    // The condition is taken as the text following 'diagnosis of' or 'diagnosed with',
+    // up to any punctuation or qualifying phrase (e.g. 'according to', 'for at least').
+
+    static RE_DIAGNOSIS: LazyLock<Regex> = LazyLock::new(|| Regex::new(
+        r"(?i)\bdiagnos(?:is|ed)\s+(?:of|with|as having)\s+([^,;:.()]+)").unwrap());
+    static RE_QUALIFIER: LazyLock<Regex> = LazyLock::new(|| Regex::new(
+        r"(?i)\s+(?:according to|for|as|by|within|in the|at least|confirmed|defined|made|who|which|that|based on|using|on)\b.*$").unwrap());
+
+    let c = RE_DIAGNOSIS.captures(text)?;
+    let condition = RE_QUALIFIER.replace(&c[1], "").trim().to_string();
+    let condition = condition.strip_prefix("a ").or(condition.strip_prefix("an ")).unwrap_or(&condition).to_string();
+    if condition.len() < 3 || condition.split_whitespace().count() > 8 {
+        None
+    }
+    else {
+        Some((c.get(0).unwrap().start(), condition))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(comparator: &str, value_low: Option<f64>, value_high: Option<f64>, units: &str) -> Limit {
+        Limit { comparator: comparator.to_string(), value_low, value_high, units: Some(units.to_string()) }
+    }
+
+    fn line(seq_num: i32, type_id: i32, text: &str) -> IECLine {
+        IECLine { seq_num, type_id, tag_type: "numdot".to_string(), tag: format!("{}.", seq_num), indent_level: 2,
+                  indent_seq_num: seq_num, sequence_string: format!("n.{:02}", seq_num), text: text.to_string() }
+    }
+
+    #[test]
+    fn check_age_limits_found() {
+        let age_limit = |text: &str| find_age_limit(text).map(|(_, l)| l);
+        assert_eq!(age_limit("Adults aged 18 years or over"), Some(limit(">=", Some(18.0), None, "years")));
+        assert_eq!(age_limit("Children aged 5 to 11 years"), Some(limit("between", Some(5.0), Some(11.0), "years")));
+        assert_eq!(age_limit("Aged between 6 and 24 months"), Some(limit("between", Some(6.0), Some(24.0), "months")));
+        assert_eq!(age_limit("Age ≥ 18 years"), Some(limit(">=", Some(18.0), None, "years")));
+        assert_eq!(age_limit("Aged under 16"), Some(limit("<", None, Some(16.0), "years")));
+        assert_eq!(age_limit("Women over the age of 50"), Some(limit(">", Some(50.0), None, "years")));
+        assert_eq!(age_limit("Men 40 years of age or older"), Some(limit(">=", Some(40.0), None, "years")));
+        assert_eq!(find_age_limit("Singleton pregnancy at 24 to 34 weeks gestation"), None);
+        assert_eq!(find_age_limit("Age-related macular degeneration in one eye"), None);
+    }
+
+    #[test]
+    fn check_pregnancy_terms_found() {
+        assert_eq!(find_pregnancy_terms("Pregnancy or breastfeeding"), vec![(0, "pregnancy"), (13, "breastfeeding")]);
+        assert_eq!(find_pregnancy_terms("Women who are pregnant or planning to become pregnant"), vec![(14, "pregnancy")]);
+        assert!(find_pregnancy_terms("Women of childbearing potential must have a negative pregnancy test").is_empty());
+    }
+
+    #[test]
+    fn check_bmi_limits_found() {
+        let bmi_limit = |text: &str| find_bmi_limit(text).map(|(_, l)| l);
+        assert_eq!(bmi_limit("BMI ≥ 30 kg/m2"), Some(limit(">=", Some(30.0), None, "kg/m2")));
+        assert_eq!(bmi_limit("Body mass index between 18.5 and 30 kg/m2"), Some(limit("between", Some(18.5), Some(30.0), "kg/m2")));
+        assert_eq!(bmi_limit("BMI (kg/m2) of 40 or more"), Some(limit(">=", Some(40.0), None, "kg/m2")));
+        assert_eq!(bmi_limit("BMI less than 18.5"), Some(limit("<", None, Some(18.5), "kg/m2")));
+        assert_eq!(find_bmi_limit("Recorded BMI in the last year"), None);
+    }
+
+    #[test]
+    fn check_lab_limits_found() {
+        let lab_limits = |text: &str| find_lab_limits(text).into_iter().map(|(_, t, l)| (t, l)).collect::<Vec<_>>();
+        assert_eq!(lab_limits("Severe renal impairment (eGFR < 30 ml/min/1.73 m2)"),
+                   vec![("eGFR", limit("<", None, Some(30.0), "ml/min/1.73 m2"))]);
+        assert_eq!(lab_limits("HbA1c between 7.0% and 10.0%"),
+                   vec![("HbA1c", limit("between", Some(7.0), Some(10.0), "%"))]);
+        assert_eq!(lab_limits("Glycated haemoglobin above 58 mmol/mol"),
+                   vec![("HbA1c", limit(">", Some(58.0), None, "mmol/mol"))]);
+        assert!(lab_limits("Creatinine clearance measured at baseline").is_empty());
+    }
+
+    #[test]
+    fn check_lab_limit_applies_to_each_test_listed() {
+        let lab_limits = |text: &str| find_lab_limits(text).into_iter().map(|(_, t, l)| (t, l)).collect::<Vec<_>>();
+        assert_eq!(lab_limits("ALT or AST > 3 x ULN"),
+                   vec![("ALT", limit(">", Some(3.0), None, "x uln")), ("AST", limit(">", Some(3.0), None, "x uln"))]);
+        assert_eq!(lab_limits("ALT, AST and/or bilirubin above 2 x ULN"),
+                   vec![("ALT", limit(">", Some(2.0), None, "x uln")), ("AST", limit(">", Some(2.0), None, "x uln")),
+                        ("bilirubin", limit(">", Some(2.0), None, "x uln"))]);
+        assert_eq!(lab_limits("Abnormal ALT, with platelets < 100 x 10^9/L"),
+                   vec![("platelets", limit("<", None, Some(100.0), "x 10^9/l"))]);
+    }
+
+    #[test]
+    fn check_diagnosis_found() {
+        let diagnosis = |text: &str| find_diagnosis(text).map(|(_, d)| d);
+        assert_eq!(diagnosis("Diagnosed with type 2 diabetes for at least 12 months"), Some("type 2 diabetes".to_string()));
+        assert_eq!(diagnosis("Confirmed diagnosis of chronic heart failure, defined as:"), Some("chronic heart failure".to_string()));
+        assert_eq!(diagnosis("A diagnosis of rheumatoid arthritis according to the 2010 ACR/EULAR criteria"), Some("rheumatoid arthritis".to_string()));
+        assert_eq!(diagnosis("Able to give informed consent"), None);
+    }
+
+    #[test]
+    fn check_negations_found() {
+        let negated = |text: &str| extract_constraints(&[line(1, 1, text)]).iter()
+                        .map(|c| (c.parameter.clone(), c.negated)).collect::<Vec<_>>();
+        assert_eq!(negated("Not pregnant or breastfeeding"),
+                   vec![("pregnancy".to_string(), true), ("breastfeeding".to_string(), true)]);
+        assert_eq!(negated("Women who are not pregnant, breastfeeding or planning a pregnancy"),
+                   vec![("pregnancy".to_string(), true), ("breastfeeding".to_string(), true)]);
+        assert_eq!(negated("Non-pregnant women"), vec![("pregnancy".to_string(), true)]);
+        assert_eq!(negated("No previous diagnosis of dementia"), vec![("dementia".to_string(), true)]);
+        assert_eq!(negated("Free of any diagnosis of epilepsy"), vec![("epilepsy".to_string(), true)]);
+        assert_eq!(negated("Aged 18 or over without a diagnosis of asthma"),
+                   vec![("age".to_string(), false), ("asthma".to_string(), true)]);
+        assert_eq!(negated("Women who aren't breastfeeding"), vec![("breastfeeding".to_string(), true)]);
+    }
+
+    #[test]
+    fn check_negation_limited_to_clause() {
+        let negated = |text: &str| extract_constraints(&[line(1, 1, text)]).iter()
+                        .map(|c| (c.parameter.clone(), c.negated)).collect::<Vec<_>>();
+        assert_eq!(negated("Pregnancy or breastfeeding"),
+                   vec![("pregnancy".to_string(), false), ("breastfeeding".to_string(), false)]);
+        assert_eq!(negated("No known allergies; diagnosis of asthma"), vec![("asthma".to_string(), false)]);
+        assert_eq!(negated("Not smoking but pregnant"), vec![("pregnancy".to_string(), false)]);
+        assert_eq!(negated("No history of any other serious illness in the last year, and a diagnosis of asthma"),
+                   vec![("asthma".to_string(), false)]);
+    }
+
+    #[test]
+    fn check_constraints_linked_to_lines() {
+        let lines = vec![
+            line(1, 1, "Aged 18 to 75 years with a diagnosis of COPD"),
+            line(2, 1, "Able to give informed consent"),
+            line(3, 2, "Pregnancy"),
+        ];
+        let constraints = extract_constraints(&lines);
+        assert_eq!(constraints.len(), 3);
+        assert_eq!((constraints[0].seq_num, constraints[0].constraint_type.as_str(), constraints[0].comparator.as_deref()),
+                   (1, "age", Some("between")));
+        assert_eq!((constraints[1].seq_num, constraints[1].constraint_type.as_str(), constraints[1].parameter.as_str()),
+                   (1, "diagnosis", "COPD"));
+        assert_eq!((constraints[2].seq_num, constraints[2].ie_type_id, constraints[2].constraint_type.as_str(), constraints[2].comparator.clone()),
+                   (3, 2, "pregnancy", None));
+    }
+}
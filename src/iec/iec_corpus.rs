@@ -0,0 +1,232 @@
+use super::parse_criteria;
+use super::iec_structs::IECLine;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// A regression corpus for the criteria parser. Each case is the criteria text of a real
+// ISRCTN record, held in iec_corpus/inclusion or iec_corpus/exclusion (which determines the
+// kind of criteria) in a text file named after the record's ISRCTN id, with the expected
+// lines in a json file of the same name. Every case is parsed and compared with its expected
+// lines, and all the cases that differ are reported together, so that the full effect of a
+// change to the parser can be seen. If the changes are the intended ones the expected files
+// can be rewritten by running the test with UPDATE_IEC_CORPUS set, i.e.
+//     UPDATE_IEC_CORPUS=1 cargo test iec_corpus
+// which also creates the json file for any newly added case.
+// Cases are added from the study json files written by a download by running the test with
+// ADD_IEC_CORPUS set to a json file or a folder (searched with its sub-folders, so that the
+// whole json data folder can be used), and optionally IEC_CORPUS_SAMPLE set to the number
+// of studies wanted, spread evenly through the files found, i.e.
+//     ADD_IEC_CORPUS=/path/to/json/files IEC_CORPUS_SAMPLE=50 cargo test iec_corpus
+// The expected lines for a new case are those the parser currently gives, and so
+// must be checked by hand (and corrected if necessary) before the case is committed.
+
+const CORPUS_FOLDER: &str = "iec_corpus";
+const KINDS: [&str; 2] = ["inclusion", "exclusion"];
+
+
+fn corpus_cases() -> Vec<(String, PathBuf)> {
+
+    let corpus_path = Path::new(env!("CARGO_MANIFEST_DIR")).join(CORPUS_FOLDER);
+    let mut cases = Vec::new();
+    for kind in KINDS {
+        let entries = fs::read_dir(corpus_path.join(kind))
+            .unwrap_or_else(|e| panic!("Unable to read the {} corpus folder: {}", kind, e));
+        let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|x| x == "txt"))
+            .collect();
+        paths.sort();
+        cases.extend(paths.into_iter().map(|p| (kind.to_string(), p)));
+    }
+    cases
+}
+
+
+fn find_json_files(folder: &Path, paths: &mut Vec<PathBuf>) {
+
+    // The json data folder holds the study files in a sub-folder for each year of registration.
+
+    let entries = fs::read_dir(folder)
+        .unwrap_or_else(|e| panic!("Unable to read {}: {}", folder.display(), e));
+    for path in entries.filter_map(|e| e.ok().map(|e| e.path())) {
+        if path.is_dir() {
+            find_json_files(&path, paths);
+        }
+        else if path.extension().is_some_and(|x| x == "json") {
+            paths.push(path);
+        }
+    }
+}
+
+
+fn spread_sample(paths: Vec<PathBuf>, sample_size: Option<usize>) -> Vec<PathBuf> {
+
+    // Takes sample_size paths at even intervals through the (sorted) list, so that the sample
+    // covers the whole life of the registry rather than just its earliest studies.
+
+    match sample_size {
+        Some(n) if n > 0 && n < paths.len() => (0..n).map(|i| paths[i * paths.len() / n].clone()).collect(),
+        _ => paths,
+    }
+}
+
+
+fn add_cases(source_path: &Path, corpus_path: &Path, sample_size: Option<usize>) -> Vec<PathBuf> {
+
+    // Writes the inclusion and exclusion text of each study json file found (or of the sample
+    // of them) as a new case, unless the study already has a case of that kind. Returns the
+    // text files written.
+
+    let mut json_paths = Vec::new();
+    if source_path.is_dir() {
+        find_json_files(source_path, &mut json_paths);
+        json_paths.sort();
+    } else {
+        json_paths.push(source_path.to_path_buf());
+    }
+
+    let mut added = Vec::new();
+    for json_path in spread_sample(json_paths, sample_size) {
+        let json_data = fs::read_to_string(&json_path)
+            .unwrap_or_else(|e| panic!("Unable to read {}: {}", json_path.display(), e));
+        let study: Value = serde_json::from_str(&json_data)
+            .unwrap_or_else(|e| panic!("Invalid study json in {}: {}", json_path.display(), e));
+        let Some(sd_sid) = study["sd_sid"].as_str() else {
+            continue;
+        };
+        for kind in KINDS {
+            if let Some(text) = study["participants"][kind].as_str() {
+                let text_path = corpus_path.join(kind).join(format!("{}.txt", sd_sid));
+                if !text.trim().is_empty() && !text_path.exists() {
+                    fs::write(&text_path, text).unwrap();
+                    added.push(text_path);
+                }
+            }
+        }
+    }
+    added
+}
+
+
+fn as_json(lines: &[IECLine]) -> String {
+    format!("{}\n", serde_json::to_string_pretty(lines).unwrap())
+}
+
+
+fn line_summary(ln: &IECLine) -> String {
+    format!("{} [type {}, level {}, tag '{}'] {}", ln.sequence_string, ln.type_id, ln.indent_level, ln.tag, ln.text)
+}
+
+
+fn describe_differences(expected: &[IECLine], actual: &[IECLine]) -> Vec<String> {
+
+    // Lines are compared position by position, which is enough to show
+    // where the output starts to differ and what the new lines are.
+
+    let mut diffs = Vec::new();
+    for i in 0..expected.len().max(actual.len()) {
+        let (e, a) = (expected.get(i), actual.get(i));
+        if e != a {
+            if let Some(e) = e {
+                diffs.push(format!("    - {}", line_summary(e)));
+            }
+            if let Some(a) = a {
+                diffs.push(format!("    + {}", line_summary(a)));
+            }
+        }
+    }
+    diffs
+}
+
+
+#[test]
+fn check_iec_corpus() {
+
+    let mut new_cases = Vec::new();
+    if let Some(source) = std::env::var_os("ADD_IEC_CORPUS") {
+        let corpus_path = Path::new(env!("CARGO_MANIFEST_DIR")).join(CORPUS_FOLDER);
+        let sample_size = std::env::var("IEC_CORPUS_SAMPLE").ok()
+            .map(|n| n.parse().expect("IEC_CORPUS_SAMPLE should be a number of studies"));
+        new_cases = add_cases(Path::new(&source), &corpus_path, sample_size);
+        println!("{} cases added to the iec corpus - check their expected lines:", new_cases.len());
+        new_cases.iter().for_each(|p| println!("    {}", p.display()));
+    }
+
+    let update = std::env::var_os("UPDATE_IEC_CORPUS").is_some();
+    let cases = corpus_cases();
+    assert!(!cases.is_empty(), "No cases found in the iec corpus");
+
+    let mut failures = Vec::new();
+    for (kind, text_path) in &cases {
+
+        let text = fs::read_to_string(text_path).unwrap();
+        let actual = parse_criteria(&text, kind);
+        let expected_path = text_path.with_extension("json");
+        let case_name = format!("{}/{}", kind, text_path.file_stem().unwrap().to_string_lossy());
+
+        if update || new_cases.contains(text_path) {
+            fs::write(&expected_path, as_json(&actual)).unwrap();
+            continue;
+        }
+
+        match fs::read_to_string(&expected_path) {
+            Ok(s) => {
+                let expected: Vec<IECLine> = serde_json::from_str(&s)
+                    .unwrap_or_else(|e| panic!("Invalid expected lines for {}: {}", case_name, e));
+                if expected != actual {
+                    failures.push(format!("{} ({} lines expected, {} found):", case_name, expected.len(), actual.len()));
+                    failures.extend(describe_differences(&expected, &actual));
+                }
+            },
+            Err(_) => failures.push(format!("{}: no expected lines (.json) file", case_name)),
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{} iec corpus cases differ from their expected lines (run with UPDATE_IEC_CORPUS=1 to accept):\n{}\n",
+            failures.iter().filter(|f| !f.starts_with("    ")).count(), failures.join("\n"));
+}
+
+
+#[test]
+fn check_cases_added_from_study_json() {
+    let temp_path = std::env::temp_dir().join("dl_isrctn_test_iec_corpus");
+    let _ = fs::remove_dir_all(&temp_path);
+    for kind in KINDS {
+        fs::create_dir_all(temp_path.join("corpus").join(kind)).unwrap();
+    }
+    let study = serde_json::json!({"sd_sid": "ISRCTN14757012",
+        "participants": {"inclusion": "1. Aged 10 - 50 years", "exclusion": null}});
+    fs::create_dir_all(temp_path.join("json/2020")).unwrap();
+    fs::write(temp_path.join("json/2020/ISRCTN14757012.json"), study.to_string()).unwrap();
+
+    let added = add_cases(&temp_path.join("json"), &temp_path.join("corpus"), None);
+    assert_eq!(added, vec![temp_path.join("corpus/inclusion/ISRCTN14757012.txt")]);
+    assert_eq!(fs::read_to_string(&added[0]).unwrap(), "1. Aged 10 - 50 years");
+    assert!(add_cases(&temp_path.join("json"), &temp_path.join("corpus"), None).is_empty());
+    fs::remove_dir_all(&temp_path).unwrap();
+}
+
+
+#[test]
+fn check_sample_spread_through_files() {
+    let paths: Vec<PathBuf> = (0..10).map(|i| PathBuf::from(format!("{}.json", i))).collect();
+    let names = |p: Vec<PathBuf>| p.iter().map(|p| p.to_string_lossy().to_string()).collect::<Vec<_>>();
+    assert_eq!(names(spread_sample(paths.clone(), Some(3))), vec!["0.json", "3.json", "6.json"]);
+    assert_eq!(spread_sample(paths.clone(), Some(20)).len(), 10);
+    assert_eq!(spread_sample(paths, None).len(), 10);
+}
+
+
+#[test]
+fn check_differences_described_by_position() {
+    let line = |seq: &str, text: &str| IECLine { seq_num: 1, type_id: 1, tag_type: "numdot".to_string(),
+                    tag: "1.".to_string(), indent_level: 2, indent_seq_num: 1,
+                    sequence_string: seq.to_string(), text: text.to_string() };
+    let expected = vec![line("n.01", "Aged 18 or over"), line("n.02", "Able to consent")];
+    let actual = vec![line("n.01", "Aged 18 or over"), line("n.02", "Able to give consent"), line("n.03", "Fluent in English")];
+    assert_eq!(describe_differences(&expected, &actual), vec![
+        "    - n.02 [type 1, level 2, tag '1.'] Able to consent".to_string(),
+        "    + n.02 [type 1, level 2, tag '1.'] Able to give consent".to_string(),
+        "    + n.03 [type 1, level 2, tag '1.'] Fluent in English".to_string(),
+    ]);
+}
@@ -1,6 +1,7 @@
 
 use super::iec_helper::*;
 use super::iec_res::*;
+use super::iec_split::*;
 use super::iec_structs::*;
 
 // use log::info;
@@ -98,8 +99,7 @@ pub fn original_process_iec(sd_sid: &String, in_string: &String, input_type: &st
     // it it corresponds to a single criterion or is itself a list of criteria.
     // If the former, the single line can be added to the 'expanded_lines', if the 
     // latter, the set of identified criteria is added.
-
-    // for now, just return the lines semi-processed...
+    // The return code continues to reflect the number of lines in the source.
  
     if null_result {
         (0, crits)
@@ -112,11 +112,9 @@ pub fn original_process_iec(sd_sid: &String, in_string: &String, input_type: &st
         else {
             return_code = if input_type == "inclusion" {8} else {32};
         }
-        (return_code, final_cr_lines)
+        let expanded_lines = split_compound_lines(final_cr_lines, &tv);
+        (return_code, expanded_lines)
     }
-
-    // to be continued...
-
 }
 
 
@@ -0,0 +1,383 @@
+use super::iec_structs::*;
+
+// Second stage of criteria processing. Each of the 'final_cr_lines' produced from the
+// carriage return separated lines may itself be a list of criteria, e.g. a single line
+// inclusion block with semicolon separated items, or a line with an inline sequence
+// such as '1. ... 2. ...' or '(a) ... (b) ...'. Such lines are split into child lines,
+// one level below the original line. Any text before the first item (a 'lead-in',
+// such as 'Patients must have:') is retained in the parent line, otherwise the parent
+// is replaced by its children. The children are examined in turn, so that nested lists
+// are also split. Loosely based on TryToSplitLine and FindSplittersInString in the C#.
+
+const MIN_ITEM_LENGTH: usize = 5;    // shorter 'items' suggest a spurious split
+const MIN_LEAD_IN_LENGTH: usize = 3;
+const MAX_SPLIT_LEVEL: i32 = 6;      // sequence_lines allows up to 8 levels
+
+const ROMAN_NUMERALS: [&str; 12] = ["i", "ii", "iii", "iv", "v", "vi", "vii", "viii", "ix", "x", "xi", "xii"];
+
+
+pub fn split_compound_lines(final_cr_lines: Vec<IECLine>, tv: &TypePars) -> Vec<IECLine> {
+
+    let mut expanded_lines: Vec<IECLine> = Vec::new();
+    for line in final_cr_lines {
+        expanded_lines.append(&mut split_line(line, tv));
+    }
+
+    // Children share their parent's seq_num, so the lines are renumbered.
+
+    for (i, line) in expanded_lines.iter_mut().enumerate() {
+        line.seq_num = i as i32 + 1;
+    }
+    expanded_lines
+}
+
+
+fn split_line(line: IECLine, tv: &TypePars) -> Vec<IECLine> {
+
+    // Header and supplementary lines (level 1) are left as they are.
+
+    if line.indent_level == 1 || line.indent_level >= MAX_SPLIT_LEVEL {
+        return vec![line];
+    }
+
+    let split = match find_split(&line.text) {
+        Some(s) => s,
+        None => return vec![line],
+    };
+
+    // Items from a level 0 ('All') line become the top level criteria (level 2), with
+    // any lead-in as a header. Otherwise items are numbered within the parent's sequence.
+
+    let (child_level, seq_base) = if line.indent_level == 0 {
+        (2, format!("{}.", tv.sequence_start))
+    }
+    else {
+        (line.indent_level + 1, format!("{}.", line.sequence_string))
+    };
+
+    let mut lines: Vec<IECLine> = Vec::new();
+    if let Some(lead_in) = split.lead_in {
+        if line.indent_level == 0 {
+            lines.push(IECLine {
+                seq_num: line.seq_num,
+                type_id: tv.grp_hdr,
+                tag_type: "none".to_string(),
+                tag: "Hdr".to_string(),
+                indent_level: 1,
+                indent_seq_num: 1,
+                sequence_string: format!("{}.H01", tv.sequence_start),
+                text: lead_in,
+            });
+        }
+        else {
+            lines.push(IECLine {
+                text: lead_in,
+                ..line.clone()
+            });
+        }
+    }
+
+    for (i, (tag, text)) in split.items.into_iter().enumerate() {
+        let child = IECLine {
+            seq_num: line.seq_num,
+            type_id: tv.type_id,
+            tag_type: split.tag_type.to_string(),
+            tag,
+            indent_level: child_level,
+            indent_seq_num: i as i32 + 1,
+            sequence_string: format!("{}{:02}", seq_base, i + 1),
+            text,
+        };
+        lines.append(&mut split_line(child, tv));
+    }
+    lines
+}
+
+
+#[derive(Debug)]
+struct SplitText {
+    start: usize,                   // position of the first separator or leader
+    tag_type: &'static str,
+    lead_in: Option<String>,
+    items: Vec<(String, String)>,   // tag and text of each item
+}
+
+
+fn find_split(text: &str) -> Option<SplitText> {
+
+    // More than one splitting mechanism may be present, e.g. a numbered list
+    // with semicolons ending each item. The one that starts first is used.
+
+    let mut splits: Vec<SplitText> = Vec::new();
+    for leader in LEADERS {
+        if let Some(s) = sequence_split(text, leader) {
+            splits.push(s);
+        }
+    }
+    if let Some(s) = separator_split(text, ';') {
+        splits.push(s);
+    }
+    else if let Some(s) = separator_split(text, ',') {
+        splits.push(s);     // commas only considered when semicolons are not used
+    }
+
+    splits.into_iter().min_by_key(|s| s.start)
+}
+
+
+#[derive(Debug, Clone, Copy)]
+enum Leader {
+    NumDot,          // 1.
+    NumBracket,      // 1)
+    NumBracketed,    // (1)
+    AlphaBracket,    // a)
+    AlphaBracketed,  // (a)
+    RomanBracket,    // i)
+    RomanBracketed,  // (i)
+}
+
+const LEADERS: [Leader; 7] = [Leader::NumDot, Leader::NumBracket, Leader::NumBracketed,
+                              Leader::AlphaBracket, Leader::AlphaBracketed,
+                              Leader::RomanBracket, Leader::RomanBracketed];
+
+impl Leader {
+
+    fn tag(&self, n: usize) -> Option<String> {
+        let alpha = || (n <= 26).then(|| ((b'a' + n as u8 - 1) as char).to_string());
+        let roman = || ROMAN_NUMERALS.get(n - 1).map(|r| r.to_string());
+        match self {
+            Leader::NumDot => Some(format!("{}.", n)),
+            Leader::NumBracket => Some(format!("{})", n)),
+            Leader::NumBracketed => Some(format!("({})", n)),
+            Leader::AlphaBracket => alpha().map(|a| format!("{})", a)),
+            Leader::AlphaBracketed => alpha().map(|a| format!("({})", a)),
+            Leader::RomanBracket => roman().map(|r| format!("{})", r)),
+            Leader::RomanBracketed => roman().map(|r| format!("({})", r)),
+        }
+    }
+
+    fn is_genuine(&self, text: &str, pos: usize, tag: &str) -> bool {
+
+        // Leaders without an opening bracket must not be part of a word or number
+        // (e.g. the '2)' in '(Appendix 2)', or the '1.' in '1.5 mg'), so must follow
+        // a space or punctuation. A '1.' followed by a digit is a decimal number.
+
+        let preceding = text[..pos].chars().last();
+        let following = text[pos + tag.len()..].chars().next();
+        match self {
+            Leader::NumBracketed | Leader::AlphaBracketed | Leader::RomanBracketed => true,
+            _ => {
+                let separated = match preceding {
+                    None => true,
+                    Some(c) => c.is_whitespace() || matches!(c, ':' | ';' | ','),
+                };
+                let not_decimal = !matches!(self, Leader::NumDot) || !following.is_some_and(|c| c.is_ascii_digit());
+                separated && not_decimal
+            },
+        }
+    }
+}
+
+
+fn find_leader(text: &str, leader: Leader, tag: &str, from: usize) -> Option<usize> {
+    text[from..].match_indices(tag)
+        .map(|(p, _)| p + from)
+        .find(|p| leader.is_genuine(text, *p, tag))
+}
+
+
+fn sequence_split(text: &str, leader: Leader) -> Option<SplitText> {
+
+    // Items are found by looking for each leader in turn (1., 2., 3.,...)
+    // after the preceding one, until the next in the sequence is not found.
+
+    let mut starts: Vec<(usize, String)> = Vec::new();
+    let mut tag = leader.tag(1)?;
+    let mut from = 0;
+    while let Some(pos) = find_leader(text, leader, &tag, from) {
+        from = pos + tag.len();
+        starts.push((pos, tag));
+        match leader.tag(starts.len() + 1) {
+            Some(t) => tag = t,
+            None => break,
+        }
+    }
+
+    if starts.len() < 2 {
+        return None;
+    }
+
+    let mut items = Vec::new();
+    for (i, (pos, tag)) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).map_or(text.len(), |(p, _)| *p);
+        let item = clean_item(&text[pos + tag.len()..end]);
+        if item.len() < MIN_ITEM_LENGTH {
+            return None;
+        }
+        items.push((tag.clone(), item));
+    }
+
+    let first = starts[0].0;
+    Some(SplitText {
+        start: first,
+        tag_type: "seq",
+        lead_in: get_lead_in(&text[..first]),
+        items,
+    })
+}
+
+
+fn separator_split(text: &str, separator: char) -> Option<SplitText> {
+
+    // Requires at least three items. Commas are very common within criteria, so for a
+    // comma separated list each item must also start with a capital and have 2 or more words.
+
+    let start = text.find(separator)?;
+    let mut parts: Vec<String> = text.split(separator).map(clean_item).filter(|p| !p.is_empty()).collect();
+    if parts.len() < 3 {
+        return None;
+    }
+
+    // A lead-in ending with a colon may precede the first item.
+
+    let mut lead_in = None;
+    if let Some(colon_pos) = parts[0].find(':') {
+        let remainder = clean_item(&parts[0][colon_pos + 1..]);
+        if !remainder.is_empty() {
+            lead_in = get_lead_in(&parts[0][..colon_pos + 1]);
+            if lead_in.is_some() {
+                parts[0] = remainder;
+            }
+        }
+    }
+
+    let plausible = |p: &String| {
+        p.len() >= MIN_ITEM_LENGTH
+            && (separator == ';' || (p.chars().next().is_some_and(|c| c.is_uppercase()) && p.contains(' ')))
+    };
+    if !parts.iter().all(plausible) {
+        return None;
+    }
+
+    Some(SplitText {
+        start,
+        tag_type: "split",
+        lead_in,
+        items: parts.into_iter().map(|p| (separator.to_string(), p)).collect(),
+    })
+}
+
+
+fn get_lead_in(s: &str) -> Option<String> {
+    let s = s.trim();
+    if s.chars().count() >= MIN_LEAD_IN_LENGTH {Some(s.to_string())} else {None}
+}
+
+
+fn clean_item(s: &str) -> String {
+
+    // Removes the separators and conjunctions that commonly end one item of a list.
+
+    let mut item = s.trim().trim_end_matches([';', ',']).trim_end();
+    for conj in [" and", " or", " and/or"] {
+        if let Some(stripped) = item.strip_suffix(conj) {
+            item = stripped.trim_end().trim_end_matches([';', ',']).trim_end();
+        }
+    }
+    item.trim_start_matches(['-', '.', ',', ')']).trim().to_string()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn single_line(text: &str, tv: &TypePars) -> IECLine {
+        IECLine {
+            seq_num: 1,
+            type_id: tv.no_sep,
+            tag_type: "none".to_string(),
+            tag: "All".to_string(),
+            indent_level: 0,
+            indent_seq_num: 1,
+            sequence_string: format!("{}.A00", tv.sequence_start),
+            text: text.to_string(),
+        }
+    }
+
+    fn summary(lines: &[IECLine]) -> Vec<(i32, i32, i32, &str, &str)> {
+        lines.iter().map(|ln| (ln.seq_num, ln.indent_level, ln.indent_seq_num,
+                                ln.sequence_string.as_str(), ln.text.as_str())).collect()
+    }
+
+    #[test]
+    fn check_semicolon_list_split_into_criteria() {
+        let tv = TypePars::new(&"ISRCTN10000001".to_string(), "inclusion");
+        let line = single_line("Aged 18 years or over; diagnosed with type 2 diabetes; able to give informed consent", &tv);
+        let lines = split_compound_lines(vec![line], &tv);
+        assert_eq!(summary(&lines), vec![
+            (1, 2, 1, "n.01", "Aged 18 years or over"),
+            (2, 2, 2, "n.02", "diagnosed with type 2 diabetes"),
+            (3, 2, 3, "n.03", "able to give informed consent"),
+        ]);
+        assert!(lines.iter().all(|ln| ln.type_id == tv.type_id && ln.tag_type == "split"));
+    }
+
+    #[test]
+    fn check_numbered_list_with_lead_in_split() {
+        let tv = TypePars::new(&"ISRCTN10000001".to_string(), "exclusion");
+        let line = single_line("Patients will be excluded if they have: 1. Pregnancy, 2. Renal impairment (eGFR < 30), and 3. Known allergy to penicillin", &tv);
+        let lines = split_compound_lines(vec![line], &tv);
+        assert_eq!(summary(&lines), vec![
+            (1, 1, 1, "e.H01", "Patients will be excluded if they have:"),
+            (2, 2, 1, "e.01", "Pregnancy"),
+            (3, 2, 2, "e.02", "Renal impairment (eGFR < 30)"),
+            (4, 2, 3, "e.03", "Known allergy to penicillin"),
+        ]);
+        assert_eq!(lines[0].type_id, tv.grp_hdr);
+        assert_eq!(lines[3].tag, "3.");
+    }
+
+    #[test]
+    fn check_nested_list_in_criterion_split_below_it() {
+        let tv = TypePars::new(&"ISRCTN10000001".to_string(), "inclusion");
+        let lines = vec![
+            IECLine { seq_num: 1, type_id: 1, tag_type: "numdot".to_string(), tag: "1.".to_string(), indent_level: 2,
+                      indent_seq_num: 1, sequence_string: "n.01".to_string(), text: "Aged 18 to 65 years".to_string() },
+            IECLine { seq_num: 2, type_id: 1, tag_type: "numdot".to_string(), tag: "2.".to_string(), indent_level: 2,
+                      indent_seq_num: 2, sequence_string: "n.02".to_string(),
+                      text: "One of the following: (a) hypertension (b) heart failure (c) previous stroke".to_string() },
+        ];
+        let lines = split_compound_lines(lines, &tv);
+        assert_eq!(summary(&lines), vec![
+            (1, 2, 1, "n.01", "Aged 18 to 65 years"),
+            (2, 2, 2, "n.02", "One of the following:"),
+            (3, 3, 1, "n.02.01", "hypertension"),
+            (4, 3, 2, "n.02.02", "heart failure"),
+            (5, 3, 3, "n.02.03", "previous stroke"),
+        ]);
+    }
+
+    #[test]
+    fn check_ordinary_criteria_not_split() {
+        let tv = TypePars::new(&"ISRCTN10000001".to_string(), "inclusion");
+        for text in ["Adults, aged 18 or over, with a BMI of 30 or more",
+                     "Dose of 1.5 mg/kg (see Appendix 2) for 2 weeks",
+                     "Resident in the UK, USA, France or Germany",
+                     "Any of: asthma; COPD"] {
+            let lines = split_compound_lines(vec![single_line(text, &tv)], &tv);
+            assert_eq!(lines.len(), 1, "{}", text);
+            assert_eq!(lines[0].sequence_string, "n.A00");
+        }
+    }
+
+    #[test]
+    fn check_capitalised_comma_list_split() {
+        let tv = TypePars::new(&"ISRCTN10000001".to_string(), "inclusion");
+        let line = single_line("Aged 18 years or over, Able to read English, Willing to attend follow up visits", &tv);
+        let lines = split_compound_lines(vec![line], &tv);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1].text, "Able to read English");
+        assert_eq!(lines[2].sequence_string, "n.03");
+    }
+}
@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 
 
 #[derive(Debug, Clone)]
@@ -11,7 +13,7 @@ pub struct CLine
 }
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct IECLine
 {
     pub seq_num: i32,
@@ -44,6 +46,26 @@ impl IECLine {
 
 
 
+// A structured constraint found in the text of a criteria line (see iec_constraints).
+// The line is identified by its seq_num and type. A negated constraint (e.g. 'not pregnant')
+// has the opposite sense to that given by the type of the line.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IECConstraint
+{
+    pub seq_num: i32,
+    pub ie_type_id: i32,
+    pub constraint_type: String,
+    pub parameter: String,
+    pub comparator: Option<String>,
+    pub value_low: Option<f64>,
+    pub value_high: Option<f64>,
+    pub units: Option<String>,
+    pub negated: bool,
+}
+
+
+
 #[allow(dead_code)]
 pub struct TypePars
 {
@@ -1,4 +1,52 @@
-pub mod iec_fns;
-pub mod iec_helper;
-pub mod iec_res;
+pub(crate) mod iec_constraints;
+pub(crate) mod iec_fns;
+pub(crate) mod iec_helper;
+pub(crate) mod iec_res;
+pub(crate) mod iec_split;
 pub mod iec_structs;
+#[cfg(test)]
+mod iec_corpus;
+
+pub use iec_structs::IECLine;
+
+use crate::helpers::string_extensions::OptionStringExtensions;
+use iec_fns::original_process_iec;
+
+// The library entry point for the inclusion / exclusion criteria parser, allowing
+// it to be used outside of an import, e.g. to check the parsing of problem records.
+// The text is cleaned and checked as during an import, so the lines returned
+// are those that would be stored in sd.study_iec. The kind of criteria is
+// one of "inclusion", "exclusion" or "eligibility" (i.e. mixed or unspecified).
+
+pub fn parse_criteria(text: &str, kind: &str) -> Vec<IECLine> {
+
+    let cleaned_text = Some(text.to_string()).clean_multiline();
+    if !cleaned_text.is_not_a_place_holder() {
+        return Vec::new();
+    }
+    match cleaned_text {
+        Some(t) => original_process_iec(&"".to_string(), &t, kind).1,
+        None => Vec::new(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_criteria_parsed_from_text() {
+        let text = "Aged 18 years or over\nDiagnosed with type 2 diabetes\nAble to give informed consent";
+        let lines = parse_criteria(text, "inclusion");
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2].sequence_string, "n.03");
+        assert_eq!(lines[2].text, "Able to give informed consent");
+    }
+
+    #[test]
+    fn check_place_holder_text_gives_no_criteria() {
+        assert!(parse_criteria("Not applicable", "exclusion").is_empty());
+        assert!(parse_criteria("  ", "exclusion").is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+use crate::data_models::json_models::{Study, SCHEMA_VERSION};
+use crate::AppError;
+use serde_json::Value;
+use std::path::Path;
+
+// Study json files are read as generic json values first, so that files written by earlier
+// versions of the program can be upgraded, one version at a time, to the current structure
+// before being deserialised into the Study model. Each change to the model that changes
+// the schema version needs a corresponding step in migrate_step.
+
+pub fn study_from_json(json_data: &str, file_path: &Path) -> Result<Study, AppError> {
+
+    let mut value: Value = serde_json::from_str(json_data)?;
+    let mut version = file_version(&value);
+
+    if version > SCHEMA_VERSION {
+        return Err(AppError::SchemaVersionError(version, file_path.to_path_buf()));
+    }
+    while version < SCHEMA_VERSION {
+        value = migrate_step(value, version);
+        version += 1;
+    }
+
+    Ok(serde_json::from_value(value)?)
+}
+
+
+fn file_version(value: &Value) -> u32 {
+
+    // Files written before versioning was introduced have no version field.
+
+    match value.get("schema_version").and_then(|v| v.as_u64()) {
+        Some(v) => v as u32,
+        None => 1,
+    }
+}
+
+
+fn migrate_step(mut value: Value, from_version: u32) -> Value {
+
+    if let Some(obj) = value.as_object_mut() {
+        if from_version == 1 {
+
+            // Version 2 added trial centres, ethics committee data and secondary
+            // outcomes, all of which are absent in version 1 files.
+
+            for field in ["secondary_outcomes", "centres", "ethics", "ethics_committees"] {
+                obj.entry(field).or_insert(Value::Null);
+            }
+            if let Some(summary) = obj.get_mut("summary").and_then(|s| s.as_object_mut()) {
+                summary.entry("secondary_outcome").or_insert(Value::Null);
+            }
+        }
+        obj.insert("schema_version".to_string(), Value::from(from_version + 1));
+    }
+    value
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn v1_study() -> Value {
+        json!({
+            "sd_sid": "ISRCTN14757012",
+            "downloaded": "2025-10-18T06:00:00",
+            "public_title": "Is surgical treatment of recurrent eye styes more or less effective when...",
+            "scientific_title": null,
+            "acronym": null,
+            "registration": { "date_id_assigned": "2020-05-24", "last_updated": "2020-06-03", "version": "89", "doi": null },
+            "identifiers": null,
+            "summary": { "plain_english_summary": null, "study_hypothesis": null, "primary_outcome": "Stye recurrence",
+                         "overall_end_date": null, "trial_website": null },
+            "primary_outcomes": null,
+            "design": { "study_design": null, "primary_study_design": null, "secondary_study_design": null },
+            "trial_types": null,
+            "conditions": null,
+            "interventions": null,
+            "contacts": null,
+            "sponsors": null,
+            "funders": null,
+            "participant_types": null,
+            "participants": { "age_range": null, "l_age_limit": null, "l_age_limit_num": null, "l_age_limit_units": null,
+                              "u_age_limit": null, "u_age_limit_num": null, "u_age_limit_units": null, "gender": null,
+                              "inclusion": null, "exclusion": null, "patient_info_sheet": null },
+            "recruitment": { "target_enrolment": null, "total_final_enrolment": null, "total_target": null,
+                             "recruitment_start": null, "recruitment_end": null,
+                             "recruitment_start_status_override": null, "recruitment_status_override": null },
+            "countries": ["India"],
+            "data_policies": null,
+            "results": { "publication_plan": null, "intent_to_publish": null, "publication_details": null,
+                         "publication_stage": null, "biomed_related": null, "basic_report": null, "plain_english_report": null },
+            "links": null,
+            "files": null,
+            "ipd": { "ipd_sharing_plan": false, "ipd_sharing_statement": null }
+        })
+    }
+
+    #[test]
+    fn check_version_1_file_is_upgraded() {
+        let json_data = v1_study().to_string();
+        let s = study_from_json(&json_data, &PathBuf::from("ISRCTN14757012.json")).unwrap();
+        assert_eq!(s.schema_version, SCHEMA_VERSION);
+        assert_eq!(s.sd_sid, "ISRCTN14757012");
+        assert!(s.centres.is_none());
+        assert!(s.summary.secondary_outcome.is_none());
+    }
+
+    #[test]
+    fn check_migration_step_sets_version_and_new_fields() {
+        let v2 = migrate_step(v1_study(), 1);
+        assert_eq!(v2["schema_version"], json!(2));
+        assert_eq!(v2["ethics_committees"], Value::Null);
+        assert!(v2["summary"].as_object().unwrap().contains_key("secondary_outcome"));
+    }
+
+    #[test]
+    fn check_newer_file_version_is_rejected() {
+        let mut v = v1_study();
+        v["schema_version"] = json!(SCHEMA_VERSION + 1);
+        let res = study_from_json(&v.to_string(), &PathBuf::from("ISRCTN14757012.json"));
+        assert!(matches!(res, Err(AppError::SchemaVersionError(_, _))));
+    }
+}
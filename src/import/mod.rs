@@ -1,48 +1,66 @@
 mod processor;
 mod support_fns;
 mod transfers;
+pub(crate) mod migration;
+mod shadow;
 
 use std::fs;
 use std::path::PathBuf;
 use crate::setup::db_pars::get_db_pool;
+use crate::setup::test_run::schema_sql;
 use crate::data_models::json_models::*;
 use crate::data_models::data_vecs::*;
 use transfers::*;
 
 use crate::AppError;
 use crate::base_types::{ImportType, ImportResult};
-use chrono::Utc;
-use log::info;
+use chrono::{NaiveDate, Utc};
+use sqlx::{Pool, Postgres};
+use log::{info, warn};
 
 #[derive(sqlx::FromRow)]
 struct FilePath {
+    sd_sid: String,
     local_path: String,
 }
 
-pub async fn import_data(import_type: &ImportType, imp_event_id:i32) -> Result<ImportResult, AppError> {
+pub async fn import_data(import_type: &ImportType, imp_event_id:i32, exclude_missing: bool) -> Result<ImportResult, AppError> {
 
     let src_pool = &get_db_pool("source").await?; // pool for the source specific db
 
     // First recreate the staging schema tables - sqlscript in file (path is relative)
 
-    let sql = include_str!("../../sql/sd_tables.sql");
+    let sql = &schema_sql(include_str!("../../sql/sd_tables.sql"));
     sqlx::raw_sql(sql).execute(src_pool)
         .await
         .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
+    prepare_import_failures(src_pool, imp_event_id).await?;
+
+    // Studies flagged (by a reconciliation) as no longer in the registry can be left out.
+
+    let missing_filter = if exclude_missing {
+        "and registry_status is distinct from 'missing'"
+    }
+    else {
+        ""
+    };
+
     // get the total number of records to be processed (depends on import type)
 
     let count_sql = match import_type {
         ImportType::Recent => {
-            r#"select count(*) from mn.source_data
-            where last_imported is null
-            or last_downloaded > last_imported"#
+            format!(r#"select count(*) from mn.source_data
+            where (last_imported is null
+            or last_downloaded > last_imported)
+            {}"#, missing_filter)
         },
         ImportType::All => {
-            "select count(*) from mn.source_data;"
+            format!("select count(*) from mn.source_data where true {}", missing_filter)
         }
-        ImportType::None => ""
+        ImportType::None => "".to_string()
     };
+    let count_sql = &schema_sql(&count_sql);
 
     // TODO - Make sure date-times are consistent (to Utc) so that date-times can be compared correctly,
     // TODO - Apply this date checking especially to download and import monitoring
@@ -55,6 +73,8 @@ pub async fn import_data(import_type: &ImportType, imp_event_id:i32) -> Result<I
     // After each batch store the data vectors into the database.
 
     let batch_size = 250;
+    let mut num_imported: i64 = 0;
+    let mut num_skipped: i64 = 0;
 
     for n in (0..num_files).step_by(batch_size) {
 
@@ -68,45 +88,68 @@ pub async fn import_data(import_type: &ImportType, imp_event_id:i32) -> Result<I
         let mut study_orgs_dv = OrgVecs::new(3*batch_size);
         let mut study_people_dv = PeopleVecs::new(3*batch_size);
         let mut study_cnts_dv = CountryVecs::new(3*batch_size);
+        let mut study_locs_dv = LocationVecs::new(3*batch_size);
+        let mut study_ethics_dv = EthicsVecs::new(2*batch_size);
+        let mut study_outcomes_dv = OutcomeVecs::new(4*batch_size);
         let mut study_conds_dv = ConditionVecs::new(2*batch_size);
         let mut study_feats_dv = FeatureVecs::new(4*batch_size);
         let mut study_tops_dv = TopicVecs::new(3*batch_size);
         let mut study_iec_dv = IECVecs::new(20*batch_size);
+        let mut study_iec_cons_dv = IECConstraintVecs::new(4*batch_size);
         let mut study_obs_dv = ObjectVecs::new(3*batch_size);
         let mut study_pubs_dv = PublicationVecs::new(3*batch_size);
         let mut study_pubs_insts_dv = PubInstanceVecs::new(3*batch_size);
         let mut import_update_dv = ImportUpdateVecs::new(batch_size);
+        let mut import_failures_dv = ImportFailureVecs::new(10);
 
         // get the list of json files relevant to this pass
 
         let file_sql = match import_type {
             ImportType::Recent => {
-                    format!(r#"select local_path from mn.source_data
-                    where last_imported is null
-                    or last_downloaded > last_imported
+
+                    // Each stored batch updates last_imported, and so drops out of
+                    // this selection - the next batch is therefore always at the start.
+                    // Files that failed earlier in this import are not updated, and so
+                    // are excluded explicitly.
+
+                    format!(r#"select sd_sid, local_path from mn.source_data
+                    where (last_imported is null
+                    or last_downloaded > last_imported)
+                    and sd_sid not in (select sd_sid from mn.import_failures
+                                       where imp_event_id = {})
+                    {}
                     ORDER BY sd_sid
-                    offset {} limit {}"#, n, batch_size)
+                    limit {}"#, imp_event_id, missing_filter, batch_size)
             },
             ImportType::All  => {
-                    format!(r#"select local_path from mn.source_data
+                    format!(r#"select sd_sid, local_path from mn.source_data
+                    where true {}
                     ORDER BY sd_sid
-                    offset {} limit {}"#, n, batch_size)
+                    offset {} limit {}"#, missing_filter, n, batch_size)
             }
             ImportType::None => "".to_string()
         };
+        let file_sql = schema_sql(&file_sql);
 
         let file_list: Vec<FilePath> = sqlx::query_as(&file_sql).fetch_all(src_pool).await
                         .map_err(|e| AppError::SqlxError(e, file_sql))?;
 
         for path in file_list {
 
-            // Deserialise the file being referenced and pass for processing.
-            
-            let p = PathBuf::from(&path.local_path);
-            let json_data = fs::read_to_string(&p)
-                .map_err(|e| AppError::IoReadErrorWithPath(e, p))?;
-            let s: Study = serde_json::from_str(&json_data)
-                .map_err(|e| AppError::SerdeError(e))?;
+            // Deserialise the file being referenced (upgrading it first if it
+            // was written using an earlier version of the model) and pass for processing.
+            // A file that cannot be read or deserialised is logged and recorded in
+            // mn.import_failures, and the import continues with the remaining files.
+
+            let s: Study = match read_study_file(&path.local_path) {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!("{} not imported: {}", path.local_path, e);
+                    import_failures_dv.add(&path.sd_sid, &path.local_path, &e.to_string(), imp_event_id);
+                    num_skipped += 1;
+                    continue;
+                }
+            };
 
             // pass s to the procesor and receive a 'database friendly' version,
             // with the data arranged to match the tables in the DB.
@@ -125,10 +168,14 @@ pub async fn import_data(import_type: &ImportType, imp_event_id:i32) -> Result<I
             if let Some(orgs) = dbs.orgs { study_orgs_dv.add(sd_sid, &orgs); }
             if let Some(peop) = dbs.people { study_people_dv.add(sd_sid, &peop); }
             if let Some(cies) = dbs.countries { study_cnts_dv.add(sd_sid, &cies); }
+            if let Some(locs) = dbs.locations { study_locs_dv.add(sd_sid, &locs); }
+            if let Some(eths) = dbs.ethics { study_ethics_dv.add(sd_sid, &eths); }
+            if let Some(outs) = dbs.outcomes { study_outcomes_dv.add(sd_sid, &outs); }
             if let Some(conds) = dbs.conditions { study_conds_dv.add(sd_sid, &conds); }
             if let Some(feats) = dbs.features { study_feats_dv.add(sd_sid, &feats); }
             if let Some(tops) = dbs.topics { study_tops_dv.add(sd_sid, &tops); }
             if let Some(iecs) = dbs.ie_crit { study_iec_dv.add(sd_sid, &iecs); }
+            if let Some(iec_cons) = dbs.iec_constraints { study_iec_cons_dv.add(sd_sid, &iec_cons); }
             if let Some(obs) = dbs.objects { study_obs_dv.add(sd_sid, &obs); }
             if let Some(pubs) = dbs.publications { study_pubs_dv.add(sd_sid, &pubs); }
             if let Some(pub_insts) = dbs.pub_instances {study_pubs_insts_dv.add(sd_sid, &pub_insts); }
@@ -143,16 +190,21 @@ pub async fn import_data(import_type: &ImportType, imp_event_id:i32) -> Result<I
         study_orgs_dv.shrink_to_fit();
         study_people_dv.shrink_to_fit();
         study_cnts_dv.shrink_to_fit();
+        study_locs_dv.shrink_to_fit();
+        study_ethics_dv.shrink_to_fit();
+        study_outcomes_dv.shrink_to_fit();
         study_conds_dv.shrink_to_fit();
         study_feats_dv.shrink_to_fit();
         study_tops_dv.shrink_to_fit();
         study_iec_dv.shrink_to_fit();
+        study_iec_cons_dv.shrink_to_fit();
         study_obs_dv.shrink_to_fit();
         study_pubs_dv.shrink_to_fit();
         study_pubs_insts_dv.shrink_to_fit();
         import_update_dv.shrink_to_fit();
 
-        studies_dv.store_data(src_pool).await?;
+        let studies_res = studies_dv.store_data(src_pool).await?;
+        num_imported += studies_res.rows_affected() as i64;
         study_dates_dv.store_data(src_pool).await?;
         study_partics_dv.store_data(src_pool).await?;
         study_titles_dv.store_data(src_pool).await?;
@@ -160,33 +212,39 @@ pub async fn import_data(import_type: &ImportType, imp_event_id:i32) -> Result<I
         study_orgs_dv.store_data(src_pool).await?;
         study_people_dv.store_data(src_pool).await?;
         study_cnts_dv.store_data(src_pool).await?;
+        study_locs_dv.store_data(src_pool).await?;
+        study_ethics_dv.store_data(src_pool).await?;
+        study_outcomes_dv.store_data(src_pool).await?;
         study_conds_dv.store_data(src_pool).await?;
         study_feats_dv.store_data(src_pool).await?;
         study_tops_dv.store_data(src_pool).await?;
         study_iec_dv.store_data(src_pool).await?;
+        study_iec_cons_dv.store_data(src_pool).await?;
         study_obs_dv.store_data(src_pool).await?;
         study_pubs_dv.store_data(src_pool).await?;
         study_pubs_insts_dv.store_data(src_pool).await?;
         import_update_dv.store_data(src_pool).await?;
-
-        if n % 250 == 0 {
-            info!("number of files processed: {}",  n);
-        }
-
-        if n > 5000 {
-            break;
+        if !import_failures_dv.is_empty() {
+            import_failures_dv.store_data(src_pool).await?;
         }
 
+        info!("number of files processed: {}",  num_imported);
     }
 
-    info!("total number of files found: {}",  num_files);
+    info!("total number of files found: {}, total imported: {}, total skipped: {}",  num_files, num_imported, num_skipped);
 
-    // Recreate the accumulated data ad schema tables - sqlscript in file (path is relative)
+    // Create a new generation of the accumulated data ad schema tables, in a shadow schema
+    // - sqlscript in file (path is relative). For a full import the new tables are filled
+    // only by the transfers below. For a recent import the records of the studies not
+    // being imported are first copied across from the live ad tables (if they exist),
+    // so that the transfers add or replace only the studies now in the sd tables
+    // (and, if missing studies are being excluded, those flagged as missing are not copied).
 
-    let sql = include_str!("../../sql/ad_tables.sql");
-    sqlx::raw_sql(sql).execute(src_pool)
-        .await
-        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+    shadow::create_build_schema(src_pool).await?;
+    if *import_type == ImportType::Recent && ad_tables_exist(src_pool).await? {
+        copy_retained_studies_to_build(src_pool, exclude_missing).await?;
+        info!("existing ad records of studies not being imported copied");
+    }
 
 
     // need to import some foreign tables to handle
@@ -201,7 +259,11 @@ pub async fn import_data(import_type: &ImportType, imp_event_id:i32) -> Result<I
     transfer_study_orgs_data(src_pool).await?;
     transfer_study_people_data(src_pool).await?;
     transfer_study_iec_data(src_pool).await?;
+    transfer_study_iec_constraints_data(src_pool).await?;
     transfer_study_countries_data(src_pool).await?;
+    transfer_study_locations_data(src_pool).await?;
+    transfer_study_ethics_data(src_pool).await?;
+    transfer_study_outcomes_data(src_pool).await?;
     transfer_study_topics_data(src_pool).await?;
     transfer_study_conditions_data2(src_pool).await?;
     transfer_study_conditions_data3(src_pool).await?;
@@ -210,10 +272,49 @@ pub async fn import_data(import_type: &ImportType, imp_event_id:i32) -> Result<I
     transfer_study_pubs_data(src_pool).await?;
     transfer_study_pub_insts_data(src_pool).await?;
 
+    // All transfers have succeeded - make the new tables the live ad tables.
+
+    shadow::swap_in_build_schema(src_pool).await?;
+    info!("new ad tables swapped in, previous tables retained in {}", shadow::prev_schema());
+
+    // The range of download dates of the files imported during this event.
+
+    let dates_sql = r#"select min(last_downloaded)::date, max(last_downloaded)::date
+                from mn.source_data
+                where last_import_id = $1"#;
+    let dates_sql = &schema_sql(dates_sql);
+    let (earliest_dl_date, latest_dl_date): (Option<NaiveDate>, Option<NaiveDate>) = sqlx::query_as(dates_sql)
+                .bind(imp_event_id).fetch_one(src_pool).await
+                .map_err(|e| AppError::SqlxError(e, dates_sql.to_string()))?;
+
     Ok(ImportResult {
         num_available: num_files,
-        num_imported: 0,
-        earliest_dl_date: Utc::now().date_naive(),
-        latest_dl_date: Utc::now().date_naive(),
+        num_imported,
+        num_skipped,
+        earliest_dl_date,
+        latest_dl_date,
     })
 }
+
+
+fn read_study_file(local_path: &str) -> Result<Study, AppError> {
+
+    let p = PathBuf::from(local_path);
+    let json_data = fs::read_to_string(&p)
+        .map_err(|e| AppError::IoReadErrorWithPath(e, p.clone()))?;
+    migration::study_from_json(&json_data, &p)
+}
+
+
+async fn prepare_import_failures(src_pool: &Pool<Postgres>, imp_event_id: i32) -> Result<(), AppError> {
+
+    // Any rows already in the quarantine list of files that could not be imported for
+    // this event id (only possible in test runs, which all use id 0) are removed, so
+    // that they do not affect the selection of files below.
+
+    let sql = &schema_sql("delete from mn.import_failures where imp_event_id = $1");
+    sqlx::query(sql).bind(imp_event_id).execute(src_pool)
+        .await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+    Ok(())
+}
@@ -3,6 +3,7 @@ use crate::data_models::db_models::*;
 use crate::helpers::string_extensions::*;
 use crate::helpers::name_extensions::*;
 use crate::iec::iec_fns::*;
+use crate::iec::iec_constraints::extract_constraints;
 use crate::iec::iec_structs::IECLine;
 
 use super::support_fns::*;
@@ -218,6 +219,19 @@ pub fn process_study_data(s: &Study) -> DBStudy {
 
     let db_countries = s.countries.clone();  // both Option<Vec<String>>
 
+    // Locations (trial centres)
+
+    let db_locs = derive_locations(&s.centres);
+
+    // Ethics approvals
+
+    let db_ethics = derive_ethics(&s.ethics, &s.ethics_committees);
+
+    // Outcome measures
+
+    let mut db_outcomes = derive_outcomes("primary", &s.primary_outcomes, &s.summary.primary_outcome);
+    db_outcomes.append(&mut derive_outcomes("secondary", &s.secondary_outcomes, &s.summary.secondary_outcome));
+
     // Conditions
 
     let db_conds = derive_conditions(&s.conditions);
@@ -241,6 +255,10 @@ pub fn process_study_data(s: &Study) -> DBStudy {
     let (db_iec, iec_flag) = derive_iec(&sd_sid, incs, excs);
     participants.iec_flag =  iec_flag;
 
+    // Structured constraints (age, pregnancy, BMI, lab values, diagnoses) within the criteria
+
+    let db_iec_constraints = db_iec.as_ref().and_then(|lines| option_from_count(extract_constraints(lines)));
+
     // Outputs and objects
 
     // First add the ISCRTN web page as an object
@@ -298,10 +316,14 @@ pub fn process_study_data(s: &Study) -> DBStudy {
         orgs: db_orgs,
         people: db_peop,
         countries: db_countries,
+        locations: db_locs,
+        ethics: db_ethics,
+        outcomes: option_from_count(db_outcomes),
         conditions: db_conds,
         features: db_feats,
         topics: db_tops,
         ie_crit: db_iec,
+        iec_constraints: db_iec_constraints,
         objects: option_from_count(db_objects),
         publications: option_from_count(link_pubs),
         pub_instances: option_from_count(link_pub_instances)
@@ -734,6 +756,105 @@ fn derive_orgs (sd_sid: &String, sponsors: &Option<Vec<StudySponsor>>,
 }
 
 
+fn derive_locations(centres: &Option<Vec<StudyCentre>>) -> Option<Vec<DBLocation>> {
+
+    // The state (or other regional name) is used to disambiguate the city name.
+    // Centres without any name, address or city are ignored.
+
+    let mut db_locs: Vec<DBLocation> = Vec::new();
+    if let Some(cs) = centres {
+        for c in cs {
+            if c.name.is_none() && c.address.is_none() && c.city.is_none() {
+                continue;
+            }
+            db_locs.push(DBLocation {
+                fac_name: c.name.clone(),
+                fac_address: c.address.clone(),
+                city_name: c.city.clone(),
+                disamb_name: c.state.clone(),
+                country_name: c.country.clone(),
+            });
+        }
+    }
+    option_from_count(db_locs)
+}
+
+
+fn derive_outcomes(outcome_type: &str, measures: &Option<Vec<OutcomeMeasure>>, outcome_text: &Option<String>) -> Vec<DBOutcome> {
+
+    // Outcomes are normally listed as separate measures. Older records instead have a single
+    // block of text describing the outcome(s) - which is stored, as the variable, only if no
+    // measures of that type are present.
+
+    let mut db_outcomes: Vec<DBOutcome> = Vec::new();
+    if let Some(oms) = measures {
+        for om in oms {
+            let variable = om.variable.clone().clean_multiline();
+            let method = om.method.clone().clean_multiline();
+            if variable.is_none() && method.is_none() {
+                continue;
+            }
+            db_outcomes.push(DBOutcome {
+                outcome_type: outcome_type.to_string(),
+                variable,
+                method,
+                timepoints: om.timepoints.clone().clean_multiline(),
+            });
+        }
+    }
+
+    if db_outcomes.is_empty()
+        && let Some(t) = outcome_text.clone().clean_multiline() {
+        db_outcomes.push(DBOutcome {
+            outcome_type: outcome_type.to_string(),
+            variable: Some(t),
+            method: None,
+            timepoints: None,
+        });
+    }
+    db_outcomes
+}
+
+
+fn derive_ethics(ethics: &Option<Ethics>, committees: &Option<Vec<EthicsCommittee>>) -> Option<Vec<DBEthics>> {
+
+    // One row per committee, each including the study level approval data. Older records
+    // may have no committee listed, only a text statement about approval - in that case
+    // a single row is created without any committee details.
+
+    let (approval_required, approval_notes) = match ethics {
+        Some(e) => (e.ethics_approval_required.clone(), e.ethics_approval.clone()),
+        None => (None, None),
+    };
+
+    let mut db_ethics: Vec<DBEthics> = Vec::new();
+    if let Some(comms) = committees {
+        for c in comms {
+            db_ethics.push(DBEthics {
+                approval_required: approval_required.clone(),
+                approval_notes: approval_notes.clone(),
+                committee_name: c.name.clone(),
+                approval_status: c.approval_status.clone(),
+                status_date: c.status_date.as_date_opt(),
+                committee_ref: c.committee_reference.clone(),
+            });
+        }
+    }
+
+    if db_ethics.is_empty() && (approval_required.is_some() || approval_notes.is_some()) {
+        db_ethics.push(DBEthics {
+            approval_required,
+            approval_notes,
+            committee_name: None,
+            approval_status: None,
+            status_date: None,
+            committee_ref: None,
+        });
+    }
+    option_from_count(db_ethics)
+}
+
+
 fn derive_conditions(conds: &Option<Vec<Condition>>) -> Option<Vec<DBCondition>>{
 
     let mut db_conds: Vec<DBCondition>= Vec::new();
@@ -0,0 +1,267 @@
+use crate::AppError;
+use crate::setup::test_run::{schema_sql, is_test_run};
+use regex::Regex;
+use sqlx::{PgConnection, Pool, Postgres};
+use std::sync::LazyLock;
+
+// The ad tables are never rebuilt in place. A new generation of the tables is built in a
+// shadow schema (ad_build), by the same sql scripts and transfer statements that would otherwise
+// write to ad, and is only swapped in, in a single transaction, once every transfer has
+// succeeded. The previous generation is kept in ad_prev until the next swap, so that it
+// can be restored if necessary. A failure part way through therefore leaves the live ad
+// tables untouched (a partly built ad_build schema is simply dropped at the next import).
+// The grants on the live schema and tables are carried over to each new generation.
+
+pub fn live_schema() -> &'static str {
+    if is_test_run() {"ad_test"} else {"ad"}
+}
+
+pub fn build_schema() -> String {
+    format!("{}_build", live_schema())
+}
+
+pub fn prev_schema() -> String {
+    format!("{}_prev", live_schema())
+}
+
+
+pub fn to_build_sql(sql: &str) -> String {
+
+    // Returns the sql to be run (i.e. after any test run schema changes)
+    // with references to the ad schema redirected to the shadow schema.
+
+    to_build_schema(&schema_sql(sql))
+}
+
+
+fn to_build_schema(sql: &str) -> String {
+    static RE_TABLE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b(ad(?:_test)?)\.").unwrap());
+    static RE_CREATE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)(schema if (?:not )?exists) (ad(?:_test)?)\b").unwrap());
+
+    let sql = RE_TABLE.replace_all(sql, "${1}_build.");
+    RE_CREATE.replace_all(&sql, "$1 ${2}_build").to_string()
+}
+
+
+pub async fn create_build_schema(src_pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    // Any shadow schema left by an earlier failed import is removed first.
+
+    let sql = &format!("SET client_min_messages TO WARNING;\nDROP SCHEMA IF EXISTS {} CASCADE;", build_schema());
+    sqlx::raw_sql(sql).execute(src_pool)
+        .await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    let sql = &to_build_sql(include_str!("../../sql/ad_tables.sql"));
+    sqlx::raw_sql(sql).execute(src_pool)
+        .await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+    Ok(())
+}
+
+
+pub async fn swap_in_build_schema(src_pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    // ad -> ad_prev and ad_build -> ad, in one transaction, so that other sessions
+    // see either the old or the new generation of the tables, never a mixture.
+    // Renaming a schema takes with it everything bound to its tables - views and foreign
+    // keys in other schemas follow the old tables into ad_prev - while the grants on the
+    // old schema and tables stay with them. The grants and default privileges are therefore
+    // repeated for the new tables, and ad_prev is only dropped if nothing else depends on it.
+
+    let (live, build, prev) = (live_schema(), build_schema(), prev_schema());
+    let mut tx = src_pool.begin().await
+        .map_err(|e| AppError::SqlxError(e, "begin".to_string()))?;
+
+    if schema_exists(&mut tx, &prev).await? {
+        drop_prev_schema(&mut tx, &prev).await?;
+    }
+
+    let live_exists = schema_exists(&mut tx, live).await?;
+    if live_exists {
+        let sql = format!("ALTER SCHEMA {} RENAME TO {}", live, prev);
+        sqlx::query(&sql).execute(&mut *tx).await
+            .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+    }
+
+    let sql = format!("ALTER SCHEMA {} RENAME TO {}", build, live);
+    sqlx::query(&sql).execute(&mut *tx).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    if live_exists {
+        copy_privileges(&mut tx, &prev, live).await?;
+    }
+
+    tx.commit().await
+        .map_err(|e| AppError::SqlxError(e, "commit".to_string()))?;
+    Ok(())
+}
+
+
+async fn schema_exists(conn: &mut PgConnection, schema: &str) -> Result<bool, AppError> {
+
+    let sql = "select exists (select 1 from pg_namespace where nspname = $1)";
+    sqlx::query_scalar(sql).bind(schema).fetch_one(conn).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))
+}
+
+
+async fn drop_prev_schema(conn: &mut PgConnection, prev: &str) -> Result<(), AppError> {
+
+    // Anything that depends on the previous tables, other than the tables themselves
+    // (e.g. a view, or a foreign key from a table in another schema), would only be removed
+    // by a cascade. Rather than lose it silently the swap fails, listing the objects, so
+    // that they can be recreated against the live tables (or dropped) before the import is rerun.
+
+    let sql = r#"select distinct pg_describe_object(d.classid, d.objid, d.objsubid)
+                from pg_depend d
+                inner join pg_class t on d.refclassid = 'pg_class'::regclass and d.refobjid = t.oid
+                where t.relnamespace = to_regnamespace($1)
+                and d.deptype = 'n'
+                and not (d.classid = 'pg_class'::regclass and d.objid in
+                    (select oid from pg_class where relnamespace = to_regnamespace($1) and relkind in ('r', 'p')))
+                order by 1"#;
+    let dependents: Vec<String> = sqlx::query_scalar(sql).bind(prev).fetch_all(&mut *conn).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+    if !dependents.is_empty() {
+        return Err(AppError::SchemaSwapError(
+            format!("Other objects depend on the tables in {}, and would be lost if they were dropped. Recreate them against the live tables, or drop them, and rerun the import.", prev),
+            format!("Dependent objects: {}", dependents.join("; "))));
+    }
+
+    // Neither drop cascades - the schema drop fails if anything else remains in it.
+
+    let sql = "select quote_ident(tablename::text) from pg_tables where schemaname = $1 order by 1";
+    let tables: Vec<String> = sqlx::query_scalar(sql).bind(prev).fetch_all(&mut *conn).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+    if !tables.is_empty() {
+        let table_list: Vec<String> = tables.iter().map(|t| format!("{}.{}", prev, t)).collect();
+        let sql = format!("DROP TABLE {}", table_list.join(", "));
+        sqlx::query(&sql).execute(&mut *conn).await
+            .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+    }
+
+    let sql = format!("DROP SCHEMA {}", prev);
+    sqlx::query(&sql).execute(&mut *conn).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+    Ok(())
+}
+
+
+#[derive(sqlx::FromRow)]
+struct Privilege {
+    object_type: String,           // 'SCHEMA', 'TABLE', or, for default privileges, pg_default_acl.defaclobjtype
+    object_name: Option<String>,   // table name, already quoted
+    for_role: Option<String>,      // role whose default privileges these are, already quoted
+    privilege_type: String,
+    grantee: String,
+    is_grantable: bool,
+}
+
+
+async fn copy_privileges(conn: &mut PgConnection, from: &str, to: &str) -> Result<(), AppError> {
+
+    // The grants on the old schema, on each of its tables that is also in the new schema, and the
+    // old schema's default privileges, are read and repeated for the new schema. An owner's own
+    // privileges are implicit and are not included.
+
+    let grantee = "case when a.grantee = 0 then 'public' else quote_ident(pg_get_userbyid(a.grantee)::text) end";
+    let sql = &format!(r#"select 'SCHEMA' as object_type, null::text as object_name, null::text as for_role,
+                a.privilege_type, {grantee} as grantee, a.is_grantable
+                from pg_namespace n cross join lateral aclexplode(n.nspacl) a
+                where n.nspname = $1 and a.grantee <> n.nspowner
+                union all
+                select 'TABLE', quote_ident(c.relname::text), null,
+                a.privilege_type, {grantee}, a.is_grantable
+                from pg_class c cross join lateral aclexplode(c.relacl) a
+                where c.relnamespace = to_regnamespace($1) and c.relkind in ('r', 'p') and a.grantee <> c.relowner
+                and exists (select 1 from pg_class b where b.relnamespace = to_regnamespace($2) and b.relname = c.relname)
+                union all
+                select d.defaclobjtype::text, null, quote_ident(pg_get_userbyid(d.defaclrole)::text),
+                a.privilege_type, {grantee}, a.is_grantable
+                from pg_default_acl d cross join lateral aclexplode(d.defaclacl) a
+                where d.defaclnamespace = to_regnamespace($1)"#);
+    let privileges: Vec<Privilege> = sqlx::query_as(sql).bind(from).bind(to).fetch_all(&mut *conn).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+    for p in &privileges {
+        if let Some(sql) = grant_sql(to, p) {
+            sqlx::query(&sql).execute(&mut *conn).await
+                .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+        }
+    }
+    Ok(())
+}
+
+
+fn grant_sql(schema: &str, p: &Privilege) -> Option<String> {
+
+    let grant_option = if p.is_grantable {" WITH GRANT OPTION"} else {""};
+    match (p.object_type.as_str(), &p.object_name, &p.for_role) {
+        ("SCHEMA", _, _) => Some(format!("GRANT {} ON SCHEMA {} TO {}{}", p.privilege_type, schema, p.grantee, grant_option)),
+        ("TABLE", Some(table), _) => Some(format!("GRANT {} ON {}.{} TO {}{}", p.privilege_type, schema, table, p.grantee, grant_option)),
+        (obj_type, _, Some(role)) => {
+            let objects = match obj_type {
+                "r" => "TABLES",
+                "S" => "SEQUENCES",
+                "f" => "FUNCTIONS",
+                "T" => "TYPES",
+                _ => return None,
+            };
+            Some(format!("ALTER DEFAULT PRIVILEGES FOR ROLE {} IN SCHEMA {} GRANT {} ON {} TO {}{}",
+                    role, schema, p.privilege_type, objects, p.grantee, grant_option))
+        },
+        _ => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_ad_tables_redirected_to_build_schema() {
+        let sql = r#"insert into ad.study_titles (sd_sid, title_text)
+            select sd_sid, title_text from sd.study_titles"#;
+        let expected = r#"insert into ad_build.study_titles (sd_sid, title_text)
+            select sd_sid, title_text from sd.study_titles"#;
+        assert_eq!(to_build_schema(sql), expected);
+    }
+
+    #[test]
+    fn check_test_schema_redirected_to_test_build_schema() {
+        let sql = "create schema if not exists ad_test;\nDROP TABLE IF EXISTS ad_test.studies;";
+        let expected = "create schema if not exists ad_test_build;\nDROP TABLE IF EXISTS ad_test_build.studies;";
+        assert_eq!(to_build_schema(sql), expected);
+    }
+
+    #[test]
+    fn check_other_names_unchanged_in_build_sql() {
+        let sql = "select s.ad_id, sd_sid from sd.studies s inner join mn.source_data m on s.sd_sid = m.sd_sid";
+        assert_eq!(to_build_schema(sql), sql);
+    }
+
+    fn privilege(object_type: &str, object_name: Option<&str>, for_role: Option<&str>, grantable: bool) -> Privilege {
+        Privilege { object_type: object_type.to_string(), object_name: object_name.map(|s| s.to_string()),
+                    for_role: for_role.map(|s| s.to_string()), privilege_type: "SELECT".to_string(),
+                    grantee: "reader".to_string(), is_grantable: grantable }
+    }
+
+    #[test]
+    fn check_schema_and_table_grants_repeated() {
+        let p = privilege("SCHEMA", None, None, false);
+        assert_eq!(grant_sql("ad", &p), Some("GRANT SELECT ON SCHEMA ad TO reader".to_string()));
+        let p = privilege("TABLE", Some("study_titles"), None, true);
+        assert_eq!(grant_sql("ad", &p), Some("GRANT SELECT ON ad.study_titles TO reader WITH GRANT OPTION".to_string()));
+    }
+
+    #[test]
+    fn check_default_privileges_repeated() {
+        let p = privilege("r", None, Some("mdr_admin"), false);
+        assert_eq!(grant_sql("ad", &p),
+            Some("ALTER DEFAULT PRIVILEGES FOR ROLE mdr_admin IN SCHEMA ad GRANT SELECT ON TABLES TO reader".to_string()));
+        let p = privilege("n", None, Some("mdr_admin"), false);
+        assert_eq!(grant_sql("ad", &p), None);
+    }
+}
@@ -1,8 +1,14 @@
 use crate::AppError;
+use crate::setup::test_run::schema_sql;
+use super::shadow::{to_build_sql, live_schema, build_schema};
 use sqlx::{Pool, Postgres};
 
+// The transfer statements are written against the ad schema, but are run against
+// the shadow schema in which the new generation of the ad tables is being built.
+
 pub async fn execute_sql(sql: &str, src_pool: &Pool<Postgres>) -> Result<(), AppError> {
 
+    let sql = &to_build_sql(sql);
     sqlx::raw_sql(sql)
         .execute(src_pool).await
         .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
@@ -11,6 +17,114 @@ pub async fn execute_sql(sql: &str, src_pool: &Pool<Postgres>) -> Result<(), App
 }
 
 
+// The ad tables that hold study based data. For a 'recent' import the rows
+// in these tables for the studies not staged in sd are copied from the live tables
+// to the shadow schema, before the staged studies are added by the transfer functions below.
+
+const AD_STUDY_TABLES: [&str; 19] = ["studies", "study_dates", "study_participants",
+    "study_titles", "study_identifiers", "study_orgs", "study_people", "study_iec", "study_iec_constraints",
+    "study_countries", "study_locations", "study_ethics", "study_outcomes", "study_topics", "study_conditions", "study_features",
+    "study_objects", "study_pubs", "study_pub_instances"];
+
+
+pub async fn ad_tables_exist(src_pool: &Pool<Postgres>) -> Result<bool, AppError> {
+
+    let sql = &schema_sql("select to_regclass('ad.studies') is not null");
+    sqlx::query_scalar(sql).fetch_one(src_pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))
+}
+
+
+#[derive(sqlx::FromRow, Debug, Clone, PartialEq)]
+struct TableColumn {
+    table_name: String,
+    column_name: String,
+    data_type: String,
+}
+
+
+async fn fetch_columns(schema: &str, src_pool: &Pool<Postgres>) -> Result<Vec<TableColumn>, AppError> {
+
+    let sql = r#"select table_name::varchar, column_name::varchar, data_type::varchar
+                from information_schema.columns
+                where table_schema = $1 and table_name = any($2)
+                order by table_name, ordinal_position"#;
+    sqlx::query_as(sql).bind(schema).bind(&AD_STUDY_TABLES[..])
+        .fetch_all(src_pool).await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))
+}
+
+
+fn structure_differences(live_cols: &[TableColumn], build_cols: &[TableColumn]) -> Vec<String> {
+
+    // Lists, table by table, the columns missing from either set or with a different
+    // data type. The order of the columns does not matter, as they are copied by name.
+
+    let mut diffs = Vec::new();
+    for table in AD_STUDY_TABLES {
+        let live: Vec<&TableColumn> = live_cols.iter().filter(|c| c.table_name == table).collect();
+        let build: Vec<&TableColumn> = build_cols.iter().filter(|c| c.table_name == table).collect();
+        if live.is_empty() {
+            diffs.push(format!("{}: table not found in the live schema", table));
+            continue;
+        }
+        for b in &build {
+            match live.iter().find(|l| l.column_name == b.column_name) {
+                None => diffs.push(format!("{}.{}: new column", table, b.column_name)),
+                Some(l) if l.data_type != b.data_type => diffs.push(format!("{}.{}: type changed from {} to {}",
+                                                                   table, b.column_name, l.data_type, b.data_type)),
+                Some(_) => {},
+            }
+        }
+        for l in live.iter().filter(|l| !build.iter().any(|b| b.column_name == l.column_name)) {
+            diffs.push(format!("{}.{}: column dropped", table, l.column_name));
+        }
+    }
+    diffs
+}
+
+
+pub async fn copy_retained_studies_to_build(src_pool: &Pool<Postgres>, exclude_missing: bool) -> Result<(), AppError> {
+
+    // Ids are retained, and each identity sequence is then moved on past the copied ids.
+    // The copy requires the live tables to have the current structure, which is checked
+    // first - if that has changed the import stops, as a full import (-I) is needed instead.
+    // The columns are listed explicitly, so their order in the two schemas does not matter.
+
+    let (live, build) = (live_schema(), build_schema());
+    let build_cols = fetch_columns(&build, src_pool).await?;
+    let diffs = structure_differences(&fetch_columns(live, src_pool).await?, &build_cols);
+    if !diffs.is_empty() {
+        return Err(AppError::TableStructureError(
+            format!("The structure of the {} tables has changed since they were created, so the records of studies not being imported cannot be copied. Run a full import (-I) instead.", live),
+            format!("Differences: {}", diffs.join("; "))));
+    }
+
+    let missing_filter = if exclude_missing {
+        r#"and not exists (select 1 from mn.source_data m
+                where m.sd_sid = a.sd_sid and m.registry_status = 'missing')"#
+    }
+    else {
+        ""
+    };
+    for table in AD_STUDY_TABLES {
+        let columns = build_cols.iter().filter(|c| c.table_name == table)
+                        .map(|c| c.column_name.as_str()).collect::<Vec<&str>>().join(", ");
+        let sql = &schema_sql(&format!(r#"insert into {build}.{table} ({columns})
+                overriding system value
+                select {columns} from {live}.{table} a
+                where not exists (select 1 from sd.studies s where s.sd_sid = a.sd_sid)
+                {missing_filter};
+                select setval(pg_get_serial_sequence('{build}.{table}', 'id'), max(id))
+                from {build}.{table} having max(id) is not null;"#));
+        sqlx::raw_sql(sql)
+            .execute(src_pool).await
+            .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+    }
+    Ok(())
+}
+
+
 pub async fn transfer_study_core_data(src_pool: &Pool<Postgres>) -> Result<(), AppError> {
 
     let sql = r#"insert into ad.studies (sd_sid, display_title, brief_description,
@@ -118,6 +232,18 @@ pub async fn transfer_study_iec_data(src_pool: &Pool<Postgres>) -> Result<(), Ap
     execute_sql(sql, src_pool).await
 }
 
+
+pub async fn transfer_study_iec_constraints_data(src_pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    let sql = r#"insert into ad.study_iec_constraints (sd_sid, seq_num, ie_type_id, constraint_type
+                    , parameter, comparator, value_low, value_high, units, negated)
+                select sd_sid, seq_num, ie_type_id, constraint_type
+                    , parameter, comparator, value_low, value_high, units, negated
+                from sd.study_iec_constraints
+                order by sd_sid"#;
+    execute_sql(sql, src_pool).await
+}
+
 pub async fn transfer_study_countries_data(src_pool: &Pool<Postgres>) -> Result<(), AppError> {
 
     let sql = r#"insert into ad.study_countries (sd_sid, country_name)
@@ -128,6 +254,40 @@ pub async fn transfer_study_countries_data(src_pool: &Pool<Postgres>) -> Result<
 }
 
 
+pub async fn transfer_study_locations_data(src_pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    let sql = r#"insert into ad.study_locations (sd_sid, fac_name, fac_address,
+                city_name, disamb_name, country_name)
+                select sd_sid, fac_name, fac_address,
+                city_name, disamb_name, country_name
+                from sd.study_locations
+                order by sd_sid"#;
+    execute_sql(sql, src_pool).await
+}
+
+
+pub async fn transfer_study_ethics_data(src_pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    let sql = r#"insert into ad.study_ethics (sd_sid, approval_required, approval_notes,
+                committee_name, approval_status, status_date, committee_ref)
+                select sd_sid, approval_required, approval_notes,
+                committee_name, approval_status, status_date, committee_ref
+                from sd.study_ethics
+                order by sd_sid"#;
+    execute_sql(sql, src_pool).await
+}
+
+
+pub async fn transfer_study_outcomes_data(src_pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    let sql = r#"insert into ad.study_outcomes (sd_sid, outcome_type, variable, method, timepoints)
+                select sd_sid, outcome_type, variable, method, timepoints
+                from sd.study_outcomes
+                order by sd_sid, id"#;
+    execute_sql(sql, src_pool).await
+}
+
+
 pub async fn transfer_study_topics_data(src_pool: &Pool<Postgres>) -> Result<(), AppError> {
 
     let sql = r#"insert into ad.study_topics (sd_sid, topic_type, original_value)
@@ -217,3 +377,44 @@ pub async fn transfer_study_pub_insts_data(src_pool: &Pool<Postgres>) -> Result<
                 order by sd_sid"#;
     execute_sql(sql, src_pool).await
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cols(table: &str, names_types: &[(&str, &str)]) -> Vec<TableColumn> {
+        names_types.iter().map(|(n, t)| TableColumn { table_name: table.to_string(),
+                column_name: n.to_string(), data_type: t.to_string() }).collect()
+    }
+
+    fn all_tables(extra: &[(&str, &str)]) -> Vec<TableColumn> {
+        let mut c = Vec::new();
+        for table in AD_STUDY_TABLES {
+            c.extend(cols(table, &[("id", "integer"), ("sd_sid", "character varying")]));
+        }
+        c.extend(cols("study_iec_constraints", extra));
+        c
+    }
+
+    #[test]
+    fn check_same_structure_gives_no_differences() {
+        let live = all_tables(&[("units", "character varying")]);
+        let mut build = live.clone();
+        build.reverse();
+        assert!(structure_differences(&live, &build).is_empty());
+    }
+
+    #[test]
+    fn check_structure_differences_listed() {
+        let live = all_tables(&[("units", "character varying"), ("value_low", "integer"), ("note", "text")]);
+        let build = all_tables(&[("units", "character varying"), ("value_low", "real"), ("negated", "boolean")]);
+        assert_eq!(structure_differences(&live, &build), vec![
+            "study_iec_constraints.value_low: type changed from integer to real".to_string(),
+            "study_iec_constraints.negated: new column".to_string(),
+            "study_iec_constraints.note: column dropped".to_string(),
+        ]);
+        let live: Vec<TableColumn> = live.into_iter().filter(|c| c.table_name != "study_pubs").collect();
+        assert!(structure_differences(&live, &build).contains(&"study_pubs: table not found in the live schema".to_string()));
+    }
+}
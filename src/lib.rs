@@ -1,23 +1,28 @@
-
 pub mod setup;
 pub mod err;
 pub mod base_types;
 pub mod recording;
 mod download;
 mod import;
+mod coding;
 mod data_models;
 mod helpers;
-mod iec;
+pub mod iec;
 
-use crate::base_types::{DownloadType, ImportType, EncodingType};
+use crate::base_types::{DownloadType, DownloadResult, ImportType, EncodingType};
 use crate::recording::events::EventRepo;
+use crate::recording::processes::MonitorRepo;
+use crate::data_models::study_diff;
 use setup::cli_reader;
+use setup::test_run;
 use setup::db_pars::get_db_pool;
 use err::AppError;
 use std::ffi::OsString;
 use std::path::PathBuf;
 use std::fs;
+use std::io;
 use chrono::NaiveDate;
+use log::info;
 
 pub async fn run(args: Vec<OsString>) -> Result<(), AppError> {
 
@@ -26,6 +31,18 @@ pub async fn run(args: Vec<OsString>) -> Result<(), AppError> {
     // the types of import and coding, and the flags and parameters associated with them.
 
     let cli_pars = cli_reader::fetch_valid_arguments(args)?;
+
+    // Parsing criteria text (the parse-iec subcommand) needs neither the config file nor
+    // the databases, so is done before either is accessed. The lines are written as json.
+
+    if let Some((kind, file_path)) = &cli_pars.iec_query {
+        let text = match file_path {
+            Some(p) => fs::read_to_string(p).map_err(|e| AppError::IoReadErrorWithPath(e, p.clone()))?,
+            None => io::read_to_string(io::stdin())?,
+        };
+        println!("{}", serde_json::to_string_pretty(&iec::parse_criteria(&text, kind))?);
+        return Ok(());
+    }
     let config_file = PathBuf::from("./app_config.toml");
     let config_string: String = fs::read_to_string(&config_file)
                                 .map_err(|e| AppError::IoReadErrorWithPath(e, config_file))?;
@@ -37,6 +54,11 @@ pub async fn run(args: Vec<OsString>) -> Result<(), AppError> {
     let mon_pool = get_db_pool("monitor").await?;  // pool for the events db
     let events = EventRepo::new(mon_pool.clone());  // events repo object
 
+    // Any event table columns used by this version of the program, but not yet in
+    // the database, are added (by the sql in sql/evs_tables.sql) before they are used.
+
+    events.update_tables().await?;
+
     // Obtain the source name, (will stop execution if the source id cannot be matched).
     // Then for the 'download recent' types, try and find a start date if one was missing.
 
@@ -46,26 +68,103 @@ pub async fn run(args: Vec<OsString>) -> Result<(), AppError> {
         params.start_date = get_start_date_from_db(&events, params.source_id).await?;
     }
 
+    // For a resumed download take the type and dates from the original event record,
+    // restarting from the end of the last period that was completed.
+
+    let mut prior_res = DownloadResult::new();
+    if let Some(dl_id) = params.resume_id {
+        let checkpoint = events.get_dl_checkpoint(dl_id).await?;
+        params.download_type = checkpoint.dl_type;
+        params.start_date = checkpoint.checkpoint_date.or(checkpoint.start_date);
+        params.end_date = checkpoint.end_date;
+        prior_res = checkpoint.res;
+    }
+
     // If reached here we are good to go. Establish log and then carry out download,
     // and / or import and / or coding as directed by the starting parameters.
 
+    // In a test run the source database sql is redirected to the test schemas, and no
+    // event records are written - the processes use an event id of 0 instead, and the
+    // results are only logged.
+
     setup::establish_log(&params)?;
+    test_run::set_test_run(params.is_test);
+    let src_pool = get_db_pool("source").await?;
+    if params.is_test {
+        test_run::create_test_monitor_table(&src_pool).await?;
+    }
+
+    // Any monitor tables or columns used by this version of the program, but not yet
+    // in the database, are added (by the sql in sql/mn_tables.sql) before they are used.
+
+    MonitorRepo::new(src_pool).update_tables().await?;
+
+    // Identifier rules in identifier_rules.toml, next to the config file, are used in
+    // place of those built into the program, so that they can be changed without a rebuild.
+
+    if download::load_id_rules(&PathBuf::from("./identifier_rules.toml"))? {
+        info!("Identifier rules read from ./identifier_rules.toml");
+    }
+
+    // A request for an archived version of a study is answered, by writing
+    // the study's json to standard output, without any other processing.
+
+    if let Some((sd_sid, as_of)) = &params.as_of_query {
+        let monitor = MonitorRepo::new(get_db_pool("source").await?);
+        match monitor.get_study_as_of(sd_sid, *as_of).await? {
+            Some(s) => println!("{}", serde_json::to_string_pretty(&s)?),
+            None => info!("No archived version of {} found for {}", sd_sid, as_of),
+        }
+        return Ok(());
+    }
+
+    // Similarly a report of the changes found during a download is written to standard output.
+    // In a test run, with no download specified, the changes found in test downloads are listed.
+
+    if let Some(dl_id) = params.changes_dl_id {
+        let dl_id = if dl_id != 0 || params.is_test {dl_id} else {events.get_last_recent_dl_id(params.source_id).await?};
+        let monitor = MonitorRepo::new(get_db_pool("source").await?);
+        let changes = monitor.get_changes(dl_id, &params.change_fields).await?;
+        println!("{}", study_diff::format_change_report(dl_id, &changes));
+        return Ok(());
+    }
+
     if params.download_type != DownloadType::None {   // a download requested
 
-        let dl_id = events.get_next_download_id(params.source_id, &params.download_type).await?;
-        let dl_res = download::download_data(&params, dl_id).await?;
-        events.update_dl_event_record (dl_id, dl_res, &params).await?;
+        let dl_id = match (params.resume_id, params.is_test) {
+            (Some(id), _) => id,
+            (None, true) => 0,
+            (None, false) => events.get_next_download_id(&params).await?,
+        };
+        let dl_res = download::download_data(&params, dl_id, prior_res, &events).await?;
+        if params.is_test {
+            info!("Test download: {} records checked, {} downloaded, {} added, {} unchanged", dl_res.num_checked, dl_res.num_downloaded, dl_res.num_added, dl_res.num_unchanged);
+        }
+        else {
+            events.update_dl_event_record (dl_id, dl_res, &params).await?;
+        }
     }
     if params.import_type != ImportType::None {     // an import requested
 
-        let imp_id = events.get_next_import_id(&params.import_type).await?;
-        let imp_res = import::import_data(&params.import_type, imp_id).await?;
-        events.update_imp_event_record (imp_id, imp_res).await?;
+        let imp_id = if params.is_test {0} else {events.get_next_import_id(&params.import_type).await?};
+        let imp_res = import::import_data(&params.import_type, imp_id, params.exclude_missing).await?;
+        if params.is_test {
+            info!("Test import: {} records available, {} imported, {} skipped", imp_res.num_available, imp_res.num_imported, imp_res.num_skipped);
+        }
+        else {
+            events.update_imp_event_record (imp_id, imp_res).await?;
+        }
     }
     if params.encoding_type != EncodingType::None {     // coding requested
 
-
-
+        let coding_id = if params.is_test {0} else {events.get_next_coding_id(params.source_id, &params.encoding_type).await?};
+        let coding_res = coding::code_data(&params.encoding_type, coding_id).await?;
+        if params.is_test {
+            info!("Test coding: {} orgs, {} countries, {} conditions coded", coding_res.num_orgs_coded, coding_res.num_countries_coded, coding_res.num_conditions_coded);
+        }
+        else {
+            events.update_coding_event_record (coding_id, coding_res).await?;
+        }
     }
 
     Ok(())
@@ -3,7 +3,7 @@ use dl_isrctn::err;
 use dl_isrctn::run;
 use std::env;
 
-#[tokio::main(flavor = "current_thread")]
+#[tokio::main(flavor = "multi_thread")]
 async fn main() {
 
     let args: Vec<_> = env::args_os().collect();
@@ -4,6 +4,18 @@ use crate::helpers::date_extensions::*;
 use sqlx::{Pool, Postgres};
 use chrono::{Utc, NaiveDate};
 
+#[derive(sqlx::FromRow)]
+struct DlEventRow {
+    dl_type: String,
+    par1: Option<String>,
+    par2: Option<String>,
+    checkpoint_date: Option<NaiveDate>,
+    num_checked: i32,
+    num_downloaded: i32,
+    num_added: i32,
+    num_unchanged: i32,
+}
+
 pub struct EventRepo {
     pub pool: Pool<Postgres>,
 }
@@ -15,6 +27,17 @@ impl EventRepo{
         }
     }
 
+    pub async fn update_tables(&self) -> Result<(), AppError> {
+
+        // Brings the event tables up to date, adding any columns used by this version
+        // of the program that are not yet present. Run once, at the start of each run.
+
+        let sql = include_str!("../../sql/evs_tables.sql");
+        sqlx::raw_sql(sql).execute(&self.pool).await
+            .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+        Ok(())
+    }
+
     pub async fn get_source_name (&self, source_id: i32) -> Option<String> {
 
         let sql = format!(r#"SELECT repo_name FROM src.parameters
@@ -24,18 +47,23 @@ impl EventRepo{
     }
 
 
-    pub async fn get_next_download_id(&self, source_id: i32, dl_type: &DownloadType) -> Result<i32, AppError>{
+    pub async fn get_next_download_id(&self, params: &InitParams) -> Result<i32, AppError>{
 
         let sql = "select coalesce(max(id), 10001) from evs.dl_events ";
         let last_id: i32 = sqlx::query_scalar(sql).fetch_one(&self.pool)
                           .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
         let new_id = last_id + 1;
 
-        // Create the new record (to be updated later).
+        // Create the new record (to be updated later). The date parameters are
+        // recorded immediately, so that an interrupted download can be resumed.
         //
+        let par_1 = params.start_date.as_string_opt();
+        let par_2 = params.end_date.as_string_opt();
         let now = Utc::now();
-        let sql = "Insert into evs.dl_events(id, source_id, dl_type, time_started) values ($1, $2, $3, $4)";
-        sqlx::query(sql).bind(new_id).bind(source_id).bind(dl_type.to_string()).bind(now)
+        let sql = r#"Insert into evs.dl_events(id, source_id, dl_type, time_started, par1, par2)
+                 values ($1, $2, $3, $4, $5, $6)"#;
+        sqlx::query(sql).bind(new_id).bind(params.source_id).bind(params.download_type.to_string()).bind(now)
+                .bind(par_1).bind(par_2)
                 .execute(&self.pool)
                 .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
 
@@ -44,26 +72,84 @@ impl EventRepo{
 
     pub async fn update_dl_event_record (&self, dl_id: i32, dl_res: DownloadResult, params: &InitParams) ->  Result<bool, AppError> {
 
-        let par_1 = params.start_date.as_string_opt();
-        let par_2 = params.end_date.as_string_opt();
         let now = Utc::now();
         let sql = r#"Update evs.dl_events set
                  time_ended = $2,
                  num_records_checked = $3,
                  num_records_downloaded = $4,
                  num_records_added = $5,
-                 par1 = $6,
-                 par2 = $7,
-                 filefolder_path = $8
+                 num_records_unchanged = $6,
+                 filefolder_path = $7
                  where id = $1"#;
         let res = sqlx::query(sql).bind(dl_id).bind(now)
                 .bind(dl_res.num_checked).bind(dl_res.num_downloaded).bind(dl_res.num_added)
-                .bind(par_1).bind(par_2).bind(params.json_data_path.to_string_lossy())
+                .bind(dl_res.num_unchanged)
+                .bind(params.json_data_path.to_string_lossy())
                 .execute(&self.pool)
                 .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
         Ok(res.rows_affected() == 1)
     }
 
+    pub async fn checkpoint_dl_event (&self, dl_id: i32, checkpoint_date: NaiveDate, dl_res: &DownloadResult) ->  Result<bool, AppError> {
+
+        // Records the end of the last completed download period, and the totals so far.
+
+        let sql = r#"Update evs.dl_events set
+                 checkpoint_date = $2,
+                 num_records_checked = $3,
+                 num_records_downloaded = $4,
+                 num_records_added = $5,
+                 num_records_unchanged = $6
+                 where id = $1"#;
+        let res = sqlx::query(sql).bind(dl_id).bind(checkpoint_date)
+                .bind(dl_res.num_checked).bind(dl_res.num_downloaded).bind(dl_res.num_added)
+                .bind(dl_res.num_unchanged)
+                .execute(&self.pool)
+                .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+        Ok(res.rows_affected() == 1)
+    }
+
+    pub async fn get_dl_checkpoint (&self, dl_id: i32) -> Result<DownloadCheckpoint, AppError> {
+
+        let sql = r#"select dl_type, par1, par2, checkpoint_date,
+                 coalesce(num_records_checked, 0) as num_checked,
+                 coalesce(num_records_downloaded, 0) as num_downloaded,
+                 coalesce(num_records_added, 0) as num_added,
+                 coalesce(num_records_unchanged, 0) as num_unchanged
+                 from evs.dl_events where id = $1"#;
+        let row: Option<DlEventRow> = sqlx::query_as(sql).bind(dl_id).fetch_optional(&self.pool)
+                .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+        let r = match row {
+            Some(r) => r,
+            None => return Err(AppError::MissingProgramParameter(format!("existing download event id (not {})", dl_id))),
+        };
+        let dl_type = DownloadType::from_event_string(&r.dl_type)
+                .ok_or_else(|| AppError::MissingProgramParameter(format!("resumable download event (event {} is '{}')", dl_id, r.dl_type)))?;
+
+        Ok(DownloadCheckpoint {
+            dl_type,
+            start_date: r.par1.and_then(|s| NaiveDate::parse_from_str(&s, "%Y-%m-%d").ok()),
+            end_date: r.par2.and_then(|s| NaiveDate::parse_from_str(&s, "%Y-%m-%d").ok()),
+            checkpoint_date: r.checkpoint_date,
+            res: DownloadResult {
+                num_checked: r.num_checked,
+                num_downloaded: r.num_downloaded,
+                num_added: r.num_added,
+                num_unchanged: r.num_unchanged,
+            },
+        })
+    }
+
+    pub async fn get_last_recent_dl_id (&self, source_id: i32) -> Result<i32, AppError> {
+
+        let sql = r#"SELECT max(id) FROM evs.dl_events
+                where source_id = $1 and dl_type = 'Recently updated'"#;
+        let dl_id: Option<i32> = sqlx::query_scalar(sql).bind(source_id).fetch_one(&self.pool)
+                .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+        dl_id.ok_or_else(|| AppError::MissingProgramParameter("a previous recent download".to_string()))
+    }
+
     pub async fn get_last_dl_recent_type_date (&self, source_id: i32) -> Option<NaiveDate> {
 
             let sql = format!(r#"SELECT max(time_ended)::date FROM evs.dl_events
@@ -98,14 +184,51 @@ impl EventRepo{
                  time_ended = $2,
                  num_records_available = $3,
                  num_records_imported = $4,
+                 num_records_skipped = $5,
                  earliest_dl_date = $6,
-                 latest_dl_date = $6
+                 latest_dl_date = $7
                  where id = $1"#;
         let res = sqlx::query(sql).bind(imp_event_id).bind(now)
-              .bind(imp_res.num_available).bind(imp_res.num_imported)
+              .bind(imp_res.num_available).bind(imp_res.num_imported).bind(imp_res.num_skipped)
               .bind(imp_res.earliest_dl_date).bind(imp_res.latest_dl_date)
               .execute(&self.pool)
               .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
         Ok(res.rows_affected() == 1)
     }
+
+
+    pub async fn get_next_coding_id(&self, source_id: i32, encoding_type: &EncodingType) -> Result<i32, AppError>{
+
+        let sql = "select coalesce(max(id), 10001) from evs.coding_events ";
+        let last_id: i32 = sqlx::query_scalar(sql).fetch_one(&self.pool)
+                          .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+        let new_id = last_id + 1;
+
+        // Create the new record (to be updated later).
+
+        let now = Utc::now();
+        let sql = "Insert into evs.coding_events(id, source_id, coding_type, time_started) values ($1, $2, $3, $4)";
+        sqlx::query(sql).bind(new_id).bind(source_id).bind(encoding_type.to_string()).bind(now)
+                .execute(&self.pool)
+                .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+        Ok(new_id)
+    }
+
+    pub async fn update_coding_event_record (&self, coding_event_id: i32, coding_res: CodingResult) ->  Result<bool, AppError> {
+
+        let now = Utc::now();
+        let sql = r#"Update evs.coding_events set
+                 time_ended = $2,
+                 num_orgs_coded = $3,
+                 num_countries_coded = $4,
+                 num_conditions_coded = $5
+                 where id = $1"#;
+        let res = sqlx::query(sql).bind(coding_event_id).bind(now)
+              .bind(coding_res.num_orgs_coded).bind(coding_res.num_countries_coded)
+              .bind(coding_res.num_conditions_coded)
+              .execute(&self.pool)
+              .await.map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+        Ok(res.rows_affected() == 1)
+    }
 }
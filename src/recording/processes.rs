@@ -1,9 +1,60 @@
 
 use crate::AppError;
+use crate::setup::test_run::schema_sql;
+use crate::data_models::json_models::Study;
+use crate::data_models::study_diff::FieldChange;
+use crate::import::migration;
 use sqlx::{Pool, Postgres};
-use std::path::PathBuf;
-use chrono::Utc;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, NaiveDate, Utc};
+use log::warn;
 
+// The details of a downloaded study file, as recorded in mn.source_data.
+
+pub struct DlRecord {
+    pub sd_sid: String,
+    pub remote_url: String,
+    pub record_date: Option<String>,
+    pub full_path: PathBuf,
+    pub content_hash: String,
+}
+
+// A distinct version of a study, as archived in mn.source_data_history.
+
+pub struct HistoryRecord {
+    pub sd_sid: String,
+    pub record_version: Option<String>,
+    pub last_updated: Option<String>,
+    pub content_hash: String,
+    pub study_json: String,
+}
+
+// A change in a field of a study, found when a new version of the study was downloaded.
+
+pub struct ChangeRecord {
+    pub sd_sid: String,
+    pub change: FieldChange,
+}
+
+#[derive(sqlx::FromRow)]
+struct StoredChange {
+    sd_sid: String,
+    field_path: String,
+    old_value: Option<String>,
+    new_value: Option<String>,
+}
+
+// The hash of the content of a study's json file, as last written, and the file's path.
+
+#[derive(sqlx::FromRow)]
+pub struct StoredFile {
+    pub sd_sid: String,
+    pub content_hash: Option<String>,
+    pub local_path: Option<String>,
+}
+
+#[derive(Clone)]
 pub struct MonitorRepo {
     pub pool: Pool<Postgres>,
 }
@@ -15,41 +66,233 @@ impl MonitorRepo{
         }
     }
 
-    pub async fn update_dl_details(&self, sd_sid: &String, remote_url: &String, dl_id: i32,
-                         record_date: &Option<String>, full_path: &PathBuf) -> Result<bool, AppError> {
+    pub async fn update_tables(&self) -> Result<(), AppError> {
 
-        let mut added = false;          // indicates if will be a new record or update of an existing one
-        let now = Utc::now();
-        let local_path = full_path.to_str().unwrap();  // assumes utf-8 characters
-
-        let sql = format!("SELECT EXISTS(SELECT 1 from mn.source_data where sd_sid = '{}')", sd_sid);
-        let mon_record_exists = sqlx::query_scalar(&sql).fetch_one(&self.pool).await
-                        .map_err(|e| AppError::SqlxError(e, sql))?;
-
-        if mon_record_exists {   // Row already exists - update with new details.
-
-            let sql = r#"Update mn.source_data set
-                        remote_url = $2,
-                        last_revised = $3::timestamp,
-                        local_path = $4,
-                        last_dl_id = $5,
-                        last_downloaded = $6
-                        where sd_sid = $1;"#;
-            sqlx::query(&sql).bind(sd_sid).bind(remote_url).bind(record_date)
-            .bind(local_path).bind(dl_id).bind(now).execute(&self.pool).await
-                    .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+        // Brings the monitor tables up to date, adding any tables and columns used by this
+        // version of the program that are not yet present. Run once, at the start of each run.
+        // Duplicate records of a study are removed by the script, so are reported first.
+
+        let sql = &schema_sql(r#"select sd_sid::varchar, count(*) from mn.source_data
+                    where sd_sid is not null group by sd_sid having count(*) > 1 order by sd_sid"#);
+        let duplicates: Vec<(String, i64)> = sqlx::query_as(sql).fetch_all(&self.pool).await
+            .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+        if !duplicates.is_empty() {
+            let listed: Vec<String> = duplicates.iter().take(20).map(|(id, n)| format!("{} ({})", id, n)).collect();
+            warn!("{} studies have more than one record in mn.source_data - all but the most recently downloaded record of each are being removed: {}{}",
+                  duplicates.len(), listed.join(", "), if duplicates.len() > 20 {", ..."} else {""});
         }
-        else {   // Create as a new record.
-
-            let sql = r#"Insert into mn.source_data(sd_sid, remote_url, last_revised,
-                    local_path, last_dl_id, last_downloaded) values ($1, $2, $3::timestamp, $4, $5, $6)"#;
-            sqlx::query(&sql).bind(sd_sid).bind(remote_url).bind(record_date)
-            .bind(local_path).bind(dl_id).bind(now).execute(&self.pool).await
-                    .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
-            added = true;
+
+        let sql = &schema_sql(include_str!("../../sql/mn_tables.sql"));
+        sqlx::raw_sql(sql).execute(&self.pool).await
+            .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+        Ok(())
+    }
+
+
+    pub async fn add_changes_batch(&self, recs: &[ChangeRecord], dl_id: i32) -> Result<(), AppError> {
+
+        let mut sd_sids: Vec<&str> = Vec::with_capacity(recs.len());
+        let mut field_paths: Vec<&str> = Vec::with_capacity(recs.len());
+        let mut old_values: Vec<Option<&str>> = Vec::with_capacity(recs.len());
+        let mut new_values: Vec<Option<&str>> = Vec::with_capacity(recs.len());
+        for r in recs {
+            sd_sids.push(&r.sd_sid);
+            field_paths.push(&r.change.field_path);
+            old_values.push(r.change.old_value.as_deref());
+            new_values.push(r.change.new_value.as_deref());
         }
 
-        Ok(added)
+        let sql = &schema_sql(r#"INSERT INTO mn.study_changes (sd_sid, dl_id, field_path, old_value, new_value)
+                    SELECT u.sd_sid, $5, u.field_path, u.old_value, u.new_value
+                    FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[])
+                         as u(sd_sid, field_path, old_value, new_value)"#);
+        sqlx::query(sql)
+            .bind(&sd_sids)
+            .bind(&field_paths)
+            .bind(&old_values)
+            .bind(&new_values)
+            .bind(dl_id)
+            .execute(&self.pool).await
+            .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+        Ok(())
+    }
+
+
+    pub async fn get_changes(&self, dl_id: i32, field_terms: &[String]) -> Result<Vec<(String, FieldChange)>, AppError> {
+
+        // Returns the changes found during a download, ordered by study, optionally restricted to
+        // those fields whose paths include any of the terms given (e.g. 'status', 'outcome').
+
+        let terms: Vec<String> = field_terms.iter().map(|t| format!("%{}%", t.to_lowercase())).collect();
+        let sql = &schema_sql(r#"select sd_sid, field_path, old_value, new_value from mn.study_changes
+                    where dl_id = $1
+                    and (cardinality($2::text[]) = 0 or lower(field_path) like any($2::text[]))
+                    order by sd_sid, id"#);
+        let rows: Vec<StoredChange> = sqlx::query_as(sql).bind(dl_id).bind(&terms)
+            .fetch_all(&self.pool).await
+            .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+        Ok(rows.into_iter().map(|r| (r.sd_sid, FieldChange {
+            field_path: r.field_path,
+            old_value: r.old_value,
+            new_value: r.new_value,
+        })).collect())
+    }
+
+
+    pub async fn add_history_batch(&self, recs: &[HistoryRecord], dl_id: i32) -> Result<i32, AppError> {
+
+        // Versions already archived are ignored. Returns the number of new versions added.
+
+        let mut sd_sids: Vec<&str> = Vec::with_capacity(recs.len());
+        let mut record_versions: Vec<Option<&str>> = Vec::with_capacity(recs.len());
+        let mut last_updateds: Vec<Option<&str>> = Vec::with_capacity(recs.len());
+        let mut content_hashes: Vec<&str> = Vec::with_capacity(recs.len());
+        let mut study_jsons: Vec<&str> = Vec::with_capacity(recs.len());
+        for r in recs {
+            sd_sids.push(&r.sd_sid);
+            record_versions.push(r.record_version.as_deref());
+            last_updateds.push(r.last_updated.as_deref());
+            content_hashes.push(&r.content_hash);
+            study_jsons.push(&r.study_json);
+        }
+
+        let sql = &schema_sql(r#"INSERT INTO mn.source_data_history (sd_sid, record_version, last_updated,
+                    content_hash, study_json, dl_id)
+                    SELECT u.sd_sid, u.record_version, u.last_updated::timestamp, u.content_hash, u.study_json::jsonb, $6
+                    FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[], $5::text[])
+                         as u(sd_sid, record_version, last_updated, content_hash, study_json)
+                    ON CONFLICT (sd_sid, content_hash) DO NOTHING"#);
+        let res = sqlx::query(sql)
+            .bind(&sd_sids)
+            .bind(&record_versions)
+            .bind(&last_updateds)
+            .bind(&content_hashes)
+            .bind(&study_jsons)
+            .bind(dl_id)
+            .execute(&self.pool).await
+            .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+        Ok(res.rows_affected() as i32)
+    }
+
+
+    pub async fn get_study_as_of(&self, sd_sid: &str, as_of: NaiveDate) -> Result<Option<Study>, AppError> {
+
+        // Returns the version of the study current at the end of the given date, i.e. the
+        // latest version last updated (or, if that is unknown, archived) on or before it.
+        // Versions archived by earlier versions of the program are upgraded as they are read.
+
+        let sql = &schema_sql(r#"select study_json::text from mn.source_data_history
+                    where sd_sid = $1
+                    and coalesce(last_updated, archived_on::timestamp) < ($2::date + 1)
+                    order by coalesce(last_updated, archived_on::timestamp) desc, id desc
+                    limit 1"#);
+        let json_data: Option<String> = sqlx::query_scalar(sql).bind(sd_sid).bind(as_of)
+            .fetch_optional(&self.pool).await
+            .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+        match json_data {
+            Some(j) => Ok(Some(migration::study_from_json(&j, Path::new(sd_sid))?)),
+            None => Ok(None),
+        }
+    }
+
+
+    pub async fn count_studies_to_flag(&self, registry_ids: &[String]) -> Result<(i64, i64), AppError> {
+
+        // Returns the number of studies in mn.source_data, and the number of those not in the
+        // list of ids currently in the registry that are not yet flagged as missing, i.e. the
+        // number that reconcile_registry_ids would flag.
+
+        let sql = &schema_sql(r#"select count(*), count(*) filter (where registry_status is distinct from 'missing'
+                                                                and not (sd_sid = any($1::text[])))
+                    from mn.source_data"#);
+        sqlx::query_as(sql).bind(registry_ids).fetch_one(&self.pool).await
+            .map_err(|e| AppError::SqlxError(e, sql.to_string()))
+    }
+
+
+    pub async fn reconcile_registry_ids(&self, registry_ids: &[String]) -> Result<(u64, u64), AppError> {
+
+        // Flags studies not in the list of ids currently in the registry as 'missing', and those
+        // previously flagged that are in the list as 'current' again. The status date is only
+        // changed when the status changes. Returns the numbers of studies flagged in each way.
+
+        let sql = &schema_sql(r#"update mn.source_data
+                    set registry_status = 'missing', status_date = current_date
+                    where registry_status is distinct from 'missing'
+                    and not (sd_sid = any($1::text[]))"#);
+        let missing = sqlx::query(sql).bind(registry_ids).execute(&self.pool).await
+            .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+        let sql = &schema_sql(r#"update mn.source_data
+                    set registry_status = 'current', status_date = current_date
+                    where registry_status = 'missing'
+                    and sd_sid = any($1::text[])"#);
+        let restored = sqlx::query(sql).bind(registry_ids).execute(&self.pool).await
+            .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+        Ok((missing.rows_affected(), restored.rows_affected()))
+    }
+
+
+    pub async fn get_stored_files(&self, sd_sids: &[&str]) -> Result<HashMap<String, StoredFile>, AppError> {
+
+        let sql = &schema_sql(r#"select sd_sid, content_hash, local_path from mn.source_data
+                    where sd_sid = any($1::text[])"#);
+        let files: Vec<StoredFile> = sqlx::query_as(sql).bind(sd_sids)
+            .fetch_all(&self.pool).await
+            .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+        Ok(files.into_iter().map(|f| (f.sd_sid.clone(), f)).collect())
+    }
+
+
+    pub async fn update_dl_details_batch(&self, recs: &[DlRecord], dl_id: i32) -> Result<i32, AppError> {
+
+        // Records the details of a batch of downloaded files, as a single upsert, and returns the
+        // number of records newly added. Requires the unique index on mn.source_data(sd_sid)
+        // created by update_tables.
+        // xmax is 0 only for rows that have just been inserted, rather than updated.
+
+        let now = Utc::now();
+        let mut sd_sids: Vec<&str> = Vec::with_capacity(recs.len());
+        let mut remote_urls: Vec<&str> = Vec::with_capacity(recs.len());
+        let mut record_dates: Vec<Option<&str>> = Vec::with_capacity(recs.len());
+        let mut local_paths: Vec<String> = Vec::with_capacity(recs.len());
+        let mut content_hashes: Vec<&str> = Vec::with_capacity(recs.len());
+        for r in recs {
+            sd_sids.push(&r.sd_sid);
+            remote_urls.push(&r.remote_url);
+            record_dates.push(r.record_date.as_deref());
+            local_paths.push(r.full_path.to_string_lossy().to_string());
+            content_hashes.push(&r.content_hash);
+        }
+
+        let sql = &schema_sql(r#"INSERT INTO mn.source_data as s (sd_sid, remote_url, last_revised,
+                    local_path, content_hash, last_dl_id, last_downloaded)
+                    SELECT u.sd_sid, u.remote_url, u.record_date::timestamp, u.local_path, u.content_hash, $6, $7
+                    FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[], $5::text[])
+                         as u(sd_sid, remote_url, record_date, local_path, content_hash)
+                    ON CONFLICT (sd_sid) DO UPDATE SET
+                    remote_url = EXCLUDED.remote_url,
+                    last_revised = EXCLUDED.last_revised,
+                    local_path = EXCLUDED.local_path,
+                    content_hash = EXCLUDED.content_hash,
+                    last_dl_id = EXCLUDED.last_dl_id,
+                    last_downloaded = EXCLUDED.last_downloaded
+                    RETURNING (xmax = 0) as added"#);
+        let added: Vec<bool> = sqlx::query_scalar(sql)
+            .bind(&sd_sids)
+            .bind(&remote_urls)
+            .bind(&record_dates)
+            .bind(&local_paths)
+            .bind(&content_hashes)
+            .bind(dl_id)
+            .bind(now)
+            .fetch_all(&self.pool).await
+            .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+
+        Ok(added.iter().filter(|a| **a).count() as i32)
     }
 
 
@@ -61,10 +304,10 @@ impl MonitorRepo{
         // Row already exists - update with new details.
 
         let now = Utc::now();
-        let sql = r#"Update mn.source_data set
+        let sql = &schema_sql(r#"Update mn.source_data set
                     last_import_id = $2,
                     last_imported = $3
-                    where sd_sid = $1;"#;
+                    where sd_sid = $1;"#);
         sqlx::query(&sql).bind(sd_sid).bind(imp_event_id).bind(now)
             .execute(&self.pool).await
             .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
@@ -73,13 +316,22 @@ impl MonitorRepo{
     }
 
 
-    #[allow(dead_code)]
-    pub async fn update_coding_details(_sd_sid: &String, _coding_event_id: i32) -> Result<(), AppError> {
+    pub async fn update_coding_details(&self, coding_event_id: i32, coding_started: DateTime<Utc>) -> Result<u64, AppError> {
 
-        // TO DO
+        // Records the coding event against each study with at least one organisation, country
+        // or condition coded during it (i.e. since it started), as a single update. Returns
+        // the number of studies updated.
 
-        Ok(())
+        let sql = &schema_sql(r#"update mn.source_data m set
+                    last_coding_id = $1,
+                    last_coded = now()
+                    where m.sd_sid in (select sd_sid from ad.study_orgs where coded_on >= $2
+                                       union select sd_sid from ad.study_countries where coded_on >= $2
+                                       union select sd_sid from ad.study_conditions where coded_on >= $2)"#);
+        let res = sqlx::query(sql).bind(coding_event_id).bind(coding_started)
+            .execute(&self.pool).await
+            .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+        Ok(res.rows_affected())
     }
 
 }
-
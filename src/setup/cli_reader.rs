@@ -1,5 +1,5 @@
 use chrono::{NaiveDate, Utc, Datelike};
-use clap::{command, Arg, ArgMatches};
+use clap::{command, Arg, ArgMatches, Command};
 use crate::base_types::{DownloadType, ImportType, EncodingType};
 use crate::err::AppError;
 use std::ffi::OsString;
@@ -12,6 +12,15 @@ pub struct CliPars {
     pub encoding_type: EncodingType,
     pub start_date: Option<NaiveDate>,
     pub end_date: Option<NaiveDate>,
+    pub save_xml_path: Option<PathBuf>,
+    pub from_xml_path: Option<PathBuf>,
+    pub resume_id: Option<i32>,
+    pub archive: bool,
+    pub as_of_query: Option<(String, NaiveDate)>,
+    pub changes_dl_id: Option<i32>,
+    pub change_fields: Vec<String>,
+    pub exclude_missing: bool,
+    pub iec_query: Option<(String, Option<PathBuf>)>,
     pub is_test: bool,
 }
 
@@ -30,12 +39,50 @@ pub fn fetch_valid_arguments(args: Vec<OsString>) -> Result<CliPars, AppError>
     let mut code_recent_flag = parse_result.get_flag("encode_flag");
     let mut code_all_flag = parse_result.get_flag("encode_all_flag");
     let test_flag = parse_result.get_flag("test_flag");
+    let archive_flag = parse_result.get_flag("archive_flag");
+    let reconcile_flag = parse_result.get_flag("reconcile_flag");
+    let exclude_missing_flag = parse_result.get_flag("exclude_missing_flag");
 
     // Dates have default values of "" so can be unwrapped
 
     let start_date_as_string = parse_result.get_one::<String>("start_date").unwrap();
     let end_date_as_string = parse_result.get_one::<String>("terminal_date").unwrap();
-    
+
+    // Xml folder paths are optional, and are not checked at this stage
+
+    let save_xml_path = parse_result.get_one::<String>("save_xml").map(PathBuf::from);
+    let from_xml_path = parse_result.get_one::<String>("from_xml").map(PathBuf::from);
+    let resume_id = parse_result.get_one::<i32>("resume").copied();
+
+    // A request for an archived version of a study is carried out instead of any other process.
+
+    let as_of = match parse_result.get_many::<String>("as_of") {
+        Some(vals) => {
+            let vals: Vec<&String> = vals.collect();
+            let as_of_date = NaiveDate::parse_from_str(vals[1], "%Y-%m-%d")
+                .map_err(|_| AppError::MissingProgramParameter("valid as-of date".to_string()))?;
+            Some((vals[0].to_string(), as_of_date))
+        },
+        None => None,
+    };
+
+    // A report of the changes found during a download is likewise carried out on its own.
+    // Without an id the most recent 'Recent' download is used (signified by 0).
+
+    let changes_id = parse_result.get_one::<i32>("changes").copied();
+    let fields: Vec<String> = match parse_result.get_one::<String>("change_fields") {
+        Some(s) => s.split(',').map(|f| f.trim().to_string()).filter(|f| !f.is_empty()).collect(),
+        None => Vec::new(),
+    };
+
+    // The parse-iec subcommand, which parses criteria text from a file (or standard input),
+    // needs neither the config file nor the databases, and is carried out on its own.
+
+    let iec_text_query = parse_result.subcommand_matches("parse-iec").map(|m| (
+        m.get_one::<String>("kind").unwrap().to_string(),     // has a default value
+        m.get_one::<String>("file").map(PathBuf::from),
+    ));
+
     // Check if a (do all recent) flag has been set 
     
     if parse_result.get_flag("do_all_recent") {
@@ -47,9 +94,11 @@ pub fn fetch_valid_arguments(args: Vec<OsString>) -> Result<CliPars, AppError>
     // If no (non-test) flags set the 'dl_updated_recently' flag.
     // This still requires an explicit or DB derived start date   
 
-    if !dl_updated_recently && !dl_updated_between_dates  
-    && !dl_created_between_dates && !dl_created_in_year 
+    if !dl_updated_recently && !dl_updated_between_dates
+    && !dl_created_between_dates && !dl_created_in_year
     && !imp_recent_flag && !imp_all_flag && !code_recent_flag && !code_all_flag
+    && from_xml_path.is_none() && resume_id.is_none() && !reconcile_flag && as_of.is_none() && changes_id.is_none()
+    && iec_text_query.is_none()
     {
         dl_updated_recently = true;
     }
@@ -140,6 +189,33 @@ pub fn fetch_valid_arguments(args: Vec<OsString>) -> Result<CliPars, AppError>
         }
     }
 
+    // A replay of saved xml takes precedence over any live download,
+    // and needs no dates, as all the saved pages in the folder are processed.
+
+    if from_xml_path.is_some() {
+        download_type = DownloadType::FromXml;
+        start_date = None;
+        end_date = None;
+    }
+
+    // A reconciliation of the study ids takes the place of any download, including a replay.
+    // It always covers the whole life of the registry, so dates are not needed.
+
+    if reconcile_flag {
+        download_type = DownloadType::Reconcile;
+        start_date = None;
+        end_date = None;
+    }
+
+    // Resuming an interrupted download takes precedence over all of these. The type
+    // and dates of the download are then obtained from the original event record.
+
+    if resume_id.is_some() {
+        download_type = DownloadType::None;
+        start_date = None;
+        end_date = None;
+    }
+
         
     Ok(CliPars {
         download_type: download_type,
@@ -147,6 +223,15 @@ pub fn fetch_valid_arguments(args: Vec<OsString>) -> Result<CliPars, AppError>
         encoding_type: encoding_type,
         start_date: start_date,
         end_date: end_date,
+        save_xml_path: save_xml_path,
+        from_xml_path: from_xml_path,
+        resume_id: resume_id,
+        archive: archive_flag,
+        as_of_query: as_of,
+        changes_dl_id: changes_id,
+        change_fields: fields,
+        exclude_missing: exclude_missing_flag,
+        iec_query: iec_text_query,
         is_test: test_flag,
     }) 
         
@@ -300,6 +385,70 @@ fn parse_args(args: Vec<OsString>) -> Result<ArgMatches, clap::Error> {
             .help("A flag indicating signifying (re)code all data")
             .action(clap::ArgAction::SetTrue)
         )
+        .arg(
+            Arg::new("save_xml")
+             .long("save-xml")
+             .required(false)
+             .help("A folder in which to save the raw xml pages returned by the API")
+        )
+        .arg(
+            Arg::new("from_xml")
+             .long("from-xml")
+             .required(false)
+             .help("A folder of previously saved xml pages, to be processed instead of calling the API")
+        )
+        .arg(
+            Arg::new("resume")
+             .long("resume")
+             .required(false)
+             .help("The id of an interrupted download event, to be continued from its last completed period")
+             .value_parser(clap::value_parser!(i32))
+        )
+        .arg(
+            Arg::new("archive_flag")
+             .long("archive")
+             .required(false)
+             .help("A flag signifying that each distinct version of the downloaded studies should be archived")
+             .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("reconcile_flag")
+             .long("reconcile")
+             .required(false)
+             .help("A flag signifying list all ids in the registry, and flag studies no longer present")
+             .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("exclude_missing_flag")
+             .long("exclude-missing")
+             .required(false)
+             .help("A flag signifying leave studies flagged as missing from the registry out of the import")
+             .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("as_of")
+             .long("as-of")
+             .required(false)
+             .num_args(2)
+             .value_names(["SD_SID", "DATE"])
+             .help("Outputs the archived version of a study as it stood on a date, as json")
+        )
+        .arg(
+            Arg::new("changes")
+             .long("changes")
+             .required(false)
+             .num_args(0..=1)
+             .default_missing_value("0")
+             .value_name("DL_ID")
+             .help("Lists the changes to studies found in a download (by default the last recent download)")
+             .value_parser(clap::value_parser!(i32))
+        )
+        .arg(
+            Arg::new("change_fields")
+             .long("fields")
+             .required(false)
+             .help("Restricts the changes listed to fields including any of these (comma separated) terms")
+        )
         .arg(
             Arg::new("test_flag")
              .short('z')
@@ -308,6 +457,23 @@ fn parse_args(args: Vec<OsString>) -> Result<ArgMatches, clap::Error> {
              .help("A flag signifying that this is part of a test run - suppresses logs")
              .action(clap::ArgAction::SetTrue)
         )
+        .subcommand(
+            Command::new("parse-iec")
+             .about("Parses inclusion / exclusion criteria text and outputs the resulting lines as json")
+             .arg(
+                Arg::new("kind")
+                 .long("kind")
+                 .required(false)
+                 .help("The type of criteria in the text")
+                 .value_parser(["inclusion", "exclusion", "eligibility"])
+                 .default_value("inclusion")
+             )
+             .arg(
+                Arg::new("file")
+                 .required(false)
+                 .help("A file containing the criteria text (if not given the text is read from standard input)")
+             )
+        )
     .try_get_matches_from(args)
 }
 
@@ -568,7 +734,7 @@ mod tests {
         let args : Vec<&str> = vec![target, "-e", "-E"];
         let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
         let res = fetch_valid_arguments(test_args).unwrap();
-        
+
         assert_eq!(res.download_type, DownloadType::None);
         assert_eq!(res.import_type, ImportType::None);
         assert_eq!(res.encoding_type, EncodingType::Recent);
@@ -576,6 +742,161 @@ mod tests {
         assert_eq!(res.end_date, None);
     }
 
+    #[test]
+    fn check_correct_pars_for_save_xml() {
+        let target = "dummy target";
+        let args : Vec<&str> = vec![target, "-r", "-s", "2020-12-04", "--save-xml", "/home/steve/Data/isrctn_xml"];
+        let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+        let res = fetch_valid_arguments(test_args).unwrap();
+
+        assert_eq!(res.download_type, DownloadType::Recent);
+        assert_eq!(res.save_xml_path, Some(PathBuf::from("/home/steve/Data/isrctn_xml")));
+        assert_eq!(res.from_xml_path, None);
+    }
+
+    #[test]
+    fn check_correct_pars_for_from_xml() {
+        let target = "dummy target";
+        let args : Vec<&str> = vec![target, "--from-xml", "/home/steve/Data/isrctn_xml"];
+        let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+        let res = fetch_valid_arguments(test_args).unwrap();
+
+        assert_eq!(res.download_type, DownloadType::FromXml);
+        assert_eq!(res.import_type, ImportType::None);
+        assert_eq!(res.from_xml_path, Some(PathBuf::from("/home/steve/Data/isrctn_xml")));
+        assert_eq!(res.start_date, None);
+        assert_eq!(res.end_date, None);
+    }
+
+    #[test]
+    fn check_from_xml_overrides_live_download() {
+        let target = "dummy target";
+        let args : Vec<&str> = vec![target, "-b", "-s", "2020-12-04", "-t", "2021-01-04", "--from-xml", "/home/steve/Data/isrctn_xml", "-I"];
+        let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+        let res = fetch_valid_arguments(test_args).unwrap();
+
+        assert_eq!(res.download_type, DownloadType::FromXml);
+        assert_eq!(res.import_type, ImportType::All);
+        assert_eq!(res.start_date, None);
+        assert_eq!(res.end_date, None);
+    }
+
+    #[test]
+    fn check_reconcile_overrides_live_download() {
+        let target = "dummy target";
+        let args : Vec<&str> = vec![target, "-y", "-s", "2019", "--reconcile", "-I", "--exclude-missing"];
+        let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+        let res = fetch_valid_arguments(test_args).unwrap();
+
+        assert_eq!(res.download_type, DownloadType::Reconcile);
+        assert_eq!(res.import_type, ImportType::All);
+        assert!(res.exclude_missing);
+        assert_eq!(res.start_date, None);
+        assert_eq!(res.end_date, None);
+    }
+
+    #[test]
+    fn check_correct_pars_for_resume() {
+        let target = "dummy target";
+        let args : Vec<&str> = vec![target, "--resume", "10123", "-i"];
+        let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+        let res = fetch_valid_arguments(test_args).unwrap();
+
+        assert_eq!(res.resume_id, Some(10123));
+        assert_eq!(res.download_type, DownloadType::None);
+        assert_eq!(res.import_type, ImportType::Recent);
+        assert_eq!(res.start_date, None);
+        assert_eq!(res.end_date, None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn check_panics_with_non_numeric_resume_id() {
+        let target = "dummy target";
+        let args : Vec<&str> = vec![target, "--resume", "abc"];
+        let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+        let _res = fetch_valid_arguments(test_args).unwrap();
+    }
+
+    #[test]
+    fn check_correct_pars_for_as_of_query() {
+        let target = "dummy target";
+        let args : Vec<&str> = vec![target, "--as-of", "ISRCTN14757012", "2021-03-15"];
+        let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+        let res = fetch_valid_arguments(test_args).unwrap();
+
+        assert_eq!(res.as_of_query, Some(("ISRCTN14757012".to_string(), NaiveDate::from_ymd_opt(2021, 3, 15).unwrap())));
+        assert_eq!(res.download_type, DownloadType::None);
+        assert_eq!(res.import_type, ImportType::None);
+        assert!(!res.archive);
+    }
+
+    #[test]
+    #[should_panic]
+    fn check_panics_with_invalid_as_of_date() {
+        let target = "dummy target";
+        let args : Vec<&str> = vec![target, "--as-of", "ISRCTN14757012", "15/03/2021"];
+        let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+        let _res = fetch_valid_arguments(test_args).unwrap();
+    }
+
+    #[test]
+    fn check_correct_pars_for_changes_report() {
+        let target = "dummy target";
+        let args : Vec<&str> = vec![target, "--changes", "--fields", "status, outcome"];
+        let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+        let res = fetch_valid_arguments(test_args).unwrap();
+
+        assert_eq!(res.changes_dl_id, Some(0));
+        assert_eq!(res.change_fields, vec!["status".to_string(), "outcome".to_string()]);
+        assert_eq!(res.download_type, DownloadType::None);
+    }
+
+    #[test]
+    fn check_changes_report_for_given_download() {
+        let target = "dummy target";
+        let args : Vec<&str> = vec![target, "--changes", "10234"];
+        let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+        let res = fetch_valid_arguments(test_args).unwrap();
+
+        assert_eq!(res.changes_dl_id, Some(10234));
+        assert!(res.change_fields.is_empty());
+    }
+
+    #[test]
+    fn check_archive_flag_with_download() {
+        let target = "dummy target";
+        let args : Vec<&str> = vec![target, "-y", "-s", "2019", "--archive"];
+        let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+        let res = fetch_valid_arguments(test_args).unwrap();
+
+        assert_eq!(res.download_type, DownloadType::ByYear);
+        assert!(res.archive);
+        assert_eq!(res.as_of_query, None);
+    }
+
+    #[test]
+    fn check_correct_pars_for_parse_iec() {
+        let target = "dummy target";
+        let args : Vec<&str> = vec![target, "parse-iec", "--kind", "exclusion", "criteria.txt"];
+        let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+        let res = fetch_valid_arguments(test_args).unwrap();
+
+        assert_eq!(res.iec_query, Some(("exclusion".to_string(), Some(PathBuf::from("criteria.txt")))));
+        assert_eq!(res.download_type, DownloadType::None);
+        assert_eq!(res.import_type, ImportType::None);
+    }
+
+    #[test]
+    fn check_parse_iec_defaults_to_inclusion_from_stdin() {
+        let target = "dummy target";
+        let args : Vec<&str> = vec![target, "parse-iec"];
+        let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+        let res = fetch_valid_arguments(test_args).unwrap();
+
+        assert_eq!(res.iec_query, Some(("inclusion".to_string(), None)));
+    }
+
 }
    
 
@@ -3,6 +3,8 @@ use toml;
 use serde::Deserialize;
 use crate::err::AppError;
 use std::path::PathBuf;
+use std::fmt::Display;
+use std::str::FromStr;
 use log::info;
 
 
@@ -11,6 +13,7 @@ pub struct TomlConfig {
     pub data: Option<TomlDataPars>,
     pub folders: Option<TomlFolderPars>,
     pub database: Option<TomlDBPars>,
+    pub api: Option<TomlApiPars>,
 }
 
 #[derive(Deserialize)]
@@ -36,10 +39,24 @@ pub struct TomlDBPars {
     pub context_db: Option<String>,
 }
 
+#[derive(Deserialize)]
+pub struct TomlApiPars {
+    pub user_agent: Option<String>,
+    pub timeout_secs: Option<String>,
+    pub connect_timeout_secs: Option<String>,
+    pub max_retries: Option<String>,
+    pub backoff_base_ms: Option<String>,
+    pub requests_per_minute: Option<String>,
+    pub burst_size: Option<String>,
+    pub page_size: Option<String>,
+    pub max_concurrent_requests: Option<String>,
+}
+
 pub struct Config {
     pub data: DataPars,
     pub folders: FolderPars,
     pub db_pars: DBPars,
+    pub api_pars: ApiPars,
 }
 
 pub struct DataPars {
@@ -63,6 +80,19 @@ pub struct DBPars {
     pub context_db: String,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApiPars {
+    pub user_agent: String,
+    pub timeout_secs: u64,
+    pub connect_timeout_secs: u64,
+    pub max_retries: u32,
+    pub backoff_base_ms: u64,
+    pub requests_per_minute: u32,
+    pub burst_size: u32,
+    pub page_size: i32,
+    pub max_concurrent_requests: usize,
+}
+
 pub static DB_PARS: OnceLock<DBPars> = OnceLock::new();
 
 pub fn populate_config_vars(config_string: &String) -> Result<Config, AppError> {
@@ -79,12 +109,27 @@ pub fn populate_config_vars(config_string: &String) -> Result<Config, AppError>
     let config_folders = verify_folder_parameters(toml_folders)?;
     let config_db_pars = verify_db_parameters(toml_database)?;
 
+    // The api section is optional - if absent all its values are defaulted.
+
+    let config_api_pars = match toml_config.api {
+        Some(toml_api) => verify_api_parameters(toml_api)?,
+        None => {
+            info!("No [api] section found in config file - using default values for api access.");
+            verify_api_parameters(TomlApiPars {
+                user_agent: None, timeout_secs: None, connect_timeout_secs: None, max_retries: None,
+                backoff_base_ms: None, requests_per_minute: None, burst_size: None, page_size: None,
+                max_concurrent_requests: None,
+            })?
+        },
+    };
+
     let _ = DB_PARS.set(config_db_pars.clone());
 
     Ok(Config{
         data: config_data,
         folders: config_folders,
         db_pars: config_db_pars,
+        api_pars: config_api_pars,
     })
 }
 
@@ -137,6 +182,35 @@ fn verify_db_parameters(toml_database: TomlDBPars) -> Result<DBPars, AppError> {
     })
 }
 
+fn verify_api_parameters(toml_api: TomlApiPars) -> Result<ApiPars, AppError> {
+
+    // All values have defaults, used if a value is missing. A value that is present, but is not
+    // a whole number greater than zero (or, for the number of retries, zero or more), stops the
+    // program, as a timeout or rate limit of zero (for instance) would make the api unusable.
+
+    let user_agent = check_defaulted_string (toml_api.user_agent, "api user agent", "dl_isrctn (MDR data harvester)");
+    let timeout_secs = check_defaulted_number (toml_api.timeout_secs, "api request timeout", "timeout_secs", 60, 1)?;
+    let connect_timeout_secs = check_defaulted_number (toml_api.connect_timeout_secs, "api connect timeout", "connect_timeout_secs", 20, 1)?;
+    let max_retries = check_defaulted_number (toml_api.max_retries, "api max retries", "max_retries", 5, 0)?;
+    let backoff_base_ms = check_defaulted_number (toml_api.backoff_base_ms, "api backoff base", "backoff_base_ms", 1000, 1)?;
+    let requests_per_minute = check_defaulted_number (toml_api.requests_per_minute, "api requests per minute", "requests_per_minute", 60, 1)?;
+    let burst_size = check_defaulted_number (toml_api.burst_size, "api burst size", "burst_size", 1, 1)?;
+    let page_size = check_defaulted_number (toml_api.page_size, "api page size", "page_size", 100, 1)?;
+    let max_concurrent_requests = check_defaulted_number (toml_api.max_concurrent_requests, "api max concurrent requests", "max_concurrent_requests", 4, 1)?;
+
+    Ok(ApiPars {
+        user_agent,
+        timeout_secs,
+        connect_timeout_secs,
+        max_retries,
+        backoff_base_ms,
+        requests_per_minute,
+        burst_size,
+        page_size,
+        max_concurrent_requests,
+    })
+}
+
 fn check_existence<T>(section: Option<T>, section_name: &str) -> Result<T, AppError> {
     section.ok_or_else(|| AppError::ConfigurationError("Missing or misspelt configuration section.".to_string(),
         format!("Cannot find a section called '[{}]'",section_name)))
@@ -152,6 +226,18 @@ fn check_essential_string (src_name: Option<String>, value_name: &str, config_na
     }
 }
 
+fn check_defaulted_number<T> (src_name: Option<String>, value_name: &str, config_name: &str, default: T, min: T) -> Result<T, AppError>
+    where T: FromStr + PartialOrd + Display {
+    let s = check_defaulted_string (src_name, value_name, &default.to_string());
+    match s.trim().parse::<T>() {
+        Ok(n) if n >= min => Ok(n),
+        _ => {
+            Err(AppError::ConfigurationError("Invalid configuration value.".to_string(),
+                format!("The value for {} ({}) must be a whole number of at least {}, but is '{}'.", value_name, config_name, min, s)))
+        },
+    }
+}
+
 fn check_defaulted_string (src_name: Option<String>, value_name: &str, default:  &str) -> String {
     match src_name {
         Some(s) if !s.trim().is_empty() => s,
@@ -431,4 +517,118 @@ context_db="cxt"
         assert_eq!(res.db_pars.context_db, "cxt");
    }
 
+
+    #[test]
+    fn check_api_defaults_are_supplied() {
+
+        let config = r#"
+
+[data]
+api_base_url = "https://www.isrctn.com/api/query/format/default?q="
+source_id = "100126"
+
+[folders]
+json_data_path="/home/steve/Data/MDR json files/isrctn"
+log_folder_path="/home/steve/Data/MDR logs/isrctn"
+
+[database]
+db_user="user_name"
+db_password="password"
+
+"#;
+        let config_string = config.to_string();
+        let res = populate_config_vars(&config_string).unwrap();
+
+        assert_eq!(res.api_pars.user_agent, "dl_isrctn (MDR data harvester)");
+        assert_eq!(res.api_pars.timeout_secs, 60);
+        assert_eq!(res.api_pars.connect_timeout_secs, 20);
+        assert_eq!(res.api_pars.max_retries, 5);
+        assert_eq!(res.api_pars.backoff_base_ms, 1000);
+        assert_eq!(res.api_pars.requests_per_minute, 60);
+        assert_eq!(res.api_pars.burst_size, 1);
+        assert_eq!(res.api_pars.page_size, 100);
+        assert_eq!(res.api_pars.max_concurrent_requests, 4);
+    }
+
+
+    #[test]
+    fn check_api_params_are_read() {
+
+        let config = r#"
+
+[data]
+api_base_url = "https://www.isrctn.com/api/query/format/default?q="
+source_id = "100126"
+
+[folders]
+json_data_path="/home/steve/Data/MDR json files/isrctn"
+log_folder_path="/home/steve/Data/MDR logs/isrctn"
+
+[database]
+db_user="user_name"
+db_password="password"
+
+[api]
+user_agent="MDR test harvester"
+timeout_secs="30"
+connect_timeout_secs="10"
+max_retries="3"
+backoff_base_ms="500"
+requests_per_minute="30"
+burst_size="4"
+page_size="50"
+max_concurrent_requests="2"
+
+"#;
+        let config_string = config.to_string();
+        let res = populate_config_vars(&config_string).unwrap();
+
+        assert_eq!(res.api_pars.user_agent, "MDR test harvester");
+        assert_eq!(res.api_pars.timeout_secs, 30);
+        assert_eq!(res.api_pars.connect_timeout_secs, 10);
+        assert_eq!(res.api_pars.max_retries, 3);
+        assert_eq!(res.api_pars.backoff_base_ms, 500);
+        assert_eq!(res.api_pars.requests_per_minute, 30);
+        assert_eq!(res.api_pars.burst_size, 4);
+        assert_eq!(res.api_pars.page_size, 50);
+        assert_eq!(res.api_pars.max_concurrent_requests, 2);
+    }
+
+    fn config_with_api_value(key: &str, value: &str) -> String {
+        format!(r#"
+[data]
+api_base_url = "https://www.isrctn.com/api/query/format/default?q="
+source_id = "100126"
+
+[folders]
+json_data_path="/home/steve/Data/MDR json files/isrctn"
+log_folder_path="/home/steve/Data/MDR logs/isrctn"
+
+[database]
+db_user="user_name"
+db_password="password"
+
+[api]
+{}="{}"
+"#, key, value)
+    }
+
+    #[test]
+    fn check_invalid_api_values_are_rejected() {
+
+        for (key, value) in [("requests_per_minute", "abc"), ("timeout_secs", "0"), ("connect_timeout_secs", "0"),
+                             ("page_size", "-10"), ("max_concurrent_requests", "0"), ("max_retries", "-1"), ("burst_size", "1.5")] {
+            match populate_config_vars(&config_with_api_value(key, value)) {
+                Err(AppError::ConfigurationError(_, d)) => assert!(d.contains(key), "{}", d),
+                _ => panic!("{} = {} should have been rejected", key, value),
+            }
+        }
+    }
+
+    #[test]
+    fn check_zero_retries_allowed() {
+        let res = populate_config_vars(&config_with_api_value("max_retries", "0")).unwrap();
+        assert_eq!(res.api_pars.max_retries, 0);
+    }
+
 }
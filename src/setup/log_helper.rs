@@ -36,6 +36,9 @@ pub fn setup_log (params: &InitParams) -> Result<log4rs::Handle, AppError> {
     if params.encoding_type != EncodingType::None {
         process_type.push_str(" CD");
     }
+    if params.is_test {
+        process_type.push_str(" TEST");
+    }
     let log_file_name = format!("ISRCTN{} {}", process_type, datetime_string);
     let log_file_path = [params.log_folder_path.clone(), PathBuf::from(&log_file_name)].iter().collect();
     config_log(&log_file_path)
@@ -100,6 +103,18 @@ pub fn log_startup_params (ip : &InitParams) {
     info!("start date: {}", sd);
     let ed = if ip.end_date == None {"none".to_string()} else {ip.end_date.unwrap().format("%Y-%m-%d").to_string()};
     info!("end date: {}", ed);
+    if let Some(p) = &ip.save_xml_path {
+        info!("saving xml to: {:?}", p);
+    }
+    if let Some(p) = &ip.from_xml_path {
+        info!("replaying xml from: {:?}", p);
+    }
+    if let Some(id) = ip.resume_id {
+        info!("resuming download event: {}", id);
+    }
+    if ip.is_test {
+        info!("TEST RUN: using test schemas, no event records written");
+    }
 
     info!("");
     info!("************************************");
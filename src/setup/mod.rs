@@ -3,6 +3,7 @@ pub mod cli_reader;
 pub mod config_reader;
 pub mod log_helper;
 pub mod db_pars;
+pub mod test_run;
 
 use std::fs;
 use std::sync::OnceLock;
@@ -24,7 +25,12 @@ pub fn get_params(cli_pars: CliPars, config_string: &String) -> Result<InitParam
     
     let config_file: Config = config_reader::populate_config_vars(&config_string)?;
     
-    let json_data_path = config_file.folders.json_data_path;
+    // In a test run json files are written to a scratch sub-folder instead.
+
+    let mut json_data_path = config_file.folders.json_data_path;
+    if cli_pars.is_test {
+        json_data_path.push(test_run::TEST_FOLDER_NAME);
+    }
     if !folder_exists(&json_data_path) {
         fs::create_dir_all(&json_data_path)?;
     }
@@ -34,10 +40,24 @@ pub fn get_params(cli_pars: CliPars, config_string: &String) -> Result<InitParam
         fs::create_dir_all(&log_folder_path)?;
     }
 
+    // A folder for saved xml is created if necessary, but a folder
+    // of xml to be replayed must already exist.
+
+    if let Some(save_xml_path) = &cli_pars.save_xml_path
+        && !folder_exists(save_xml_path) {
+        fs::create_dir_all(save_xml_path)?;
+    }
+
+    if let Some(from_xml_path) = &cli_pars.from_xml_path
+        && !folder_exists(from_xml_path) {
+        return Err(AppError::MissingProgramParameter("existing folder of saved xml files".to_string()));
+    }
+
     Ok(InitParams {
         source_id: config_file.data.source_id,
         source_name: "".to_string(), // to be added later, in lib
         api_base_url: config_file.data.api_base_url,
+        api_pars: config_file.api_pars,
         json_data_path: json_data_path,
         log_folder_path: log_folder_path,
         download_type: cli_pars.download_type,
@@ -45,6 +65,14 @@ pub fn get_params(cli_pars: CliPars, config_string: &String) -> Result<InitParam
         encoding_type: cli_pars.encoding_type,
         start_date: cli_pars.start_date,
         end_date:cli_pars.end_date,
+        save_xml_path: cli_pars.save_xml_path,
+        from_xml_path: cli_pars.from_xml_path,
+        resume_id: cli_pars.resume_id,
+        archive: cli_pars.archive,
+        as_of_query: cli_pars.as_of_query,
+        changes_dl_id: cli_pars.changes_dl_id,
+        change_fields: cli_pars.change_fields,
+        exclude_missing: cli_pars.exclude_missing,
         is_test: cli_pars.is_test,
     })
 
@@ -205,4 +233,39 @@ context_db="cxt"
         assert_eq!(res.end_date, None);
     }
 
+
+    #[test]
+    fn check_test_run_uses_scratch_json_folder() {
+        let config = r#"
+[data]
+api_base_url = "https://www.isrctn.com/api/query/format/default?q="
+source_id = "100126"
+
+[folders]
+json_data_path="/home/steve/Data/MDR json files/isrctn"
+log_folder_path="/home/steve/Data/MDR logs/isrctn"
+
+[database]
+db_host="localhost"
+db_user="pg_user"
+db_password="foo"
+db_port="5432"
+source_db="isrctn"
+monitor_db="mon"
+context_db="cxt"
+        "#;
+        let config_string = config.to_string();
+        config_reader::populate_config_vars(&config_string).unwrap();
+
+        let args : Vec<&str> = vec!["dummy target", "-i", "-z"];
+        let test_args = args.iter().map(|x| x.to_string().into()).collect::<Vec<OsString>>();
+        let cli_pars = cli_reader::fetch_valid_arguments(test_args).unwrap();
+        let res = get_params(cli_pars, &config_string).unwrap();
+
+        assert!(res.is_test);
+        assert_eq!(res.json_data_path, PathBuf::from("/home/steve/Data/MDR json files/isrctn/test_run"));
+        assert_eq!(res.log_folder_path, PathBuf::from("/home/steve/Data/MDR logs/isrctn"));
+        assert_eq!(res.import_type, ImportType::Recent);
+    }
+
 }
@@ -0,0 +1,91 @@
+use crate::err::AppError;
+use regex::Regex;
+use sqlx::{Pool, Postgres};
+use std::sync::{LazyLock, OnceLock};
+
+// A test run (-z in the CLI) is a dry run of the normal processes. Json files are
+// written to a scratch sub-folder of the json data folder, and all the sql run against
+// the source database has its schema names changed, so that sd, ad, mn and lup become
+// sd_test, ad_test, mn_test and lup_test. No download, import or coding event records
+// are created in the monitor database. The production data is therefore left untouched.
+
+pub static TEST_RUN: OnceLock<bool> = OnceLock::new();
+
+pub const TEST_FOLDER_NAME: &str = "test_run";
+
+pub fn set_test_run(is_test: bool) {
+    let _ = TEST_RUN.set(is_test);  // already set if run is called more than once (integration tests)
+}
+
+pub fn is_test_run() -> bool {
+    matches!(TEST_RUN.get(), Some(true))
+}
+
+
+pub fn schema_sql(sql: &str) -> String {
+
+    // Returns the sql to be run, i.e. with the test schema names if this is a test run.
+
+    if is_test_run() {
+        to_test_schemas(sql)
+    }
+    else {
+        sql.to_string()
+    }
+}
+
+
+fn to_test_schemas(sql: &str) -> String {
+    static RE_SCHEMA: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b(sd|ad|mn|lup)\.").unwrap());
+    static RE_CREATE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)(schema if (?:not )?exists) (sd|ad|mn|lup)\b").unwrap());
+
+    let sql = RE_SCHEMA.replace_all(sql, "${1}_test.");
+    RE_CREATE.replace_all(&sql, "$1 ${2}_test").to_string()
+}
+
+
+pub async fn create_test_monitor_table(src_pool: &Pool<Postgres>) -> Result<(), AppError> {
+
+    // The monitor table used in a test run has the same structure (including
+    // indexes) as the production table, but starts empty in a new test schema.
+
+    let sql = r#"SET client_min_messages TO WARNING;
+        create schema if not exists mn_test;
+        create table if not exists mn_test.source_data (like mn.source_data including all);"#;
+    sqlx::raw_sql(sql).execute(src_pool)
+        .await
+        .map_err(|e| AppError::SqlxError(e, sql.to_string()))?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_schema_names_changed_in_test_sql() {
+        let sql = r#"insert into ad.study_titles (sd_sid, title_text)
+            select sd_sid, title_text from sd.study_titles s
+            inner join mn.source_data m on s.sd_sid = m.sd_sid"#;
+        let expected = r#"insert into ad_test.study_titles (sd_sid, title_text)
+            select sd_sid, title_text from sd_test.study_titles s
+            inner join mn_test.source_data m on s.sd_sid = m.sd_sid"#;
+        assert_eq!(to_test_schemas(sql), expected);
+    }
+
+    #[test]
+    fn check_schema_creation_changed_in_test_sql() {
+        let sql = "SET client_min_messages TO WARNING;\ncreate schema if not exists sd;\nDROP TABLE IF EXISTS sd.studies;";
+        let expected = "SET client_min_messages TO WARNING;\ncreate schema if not exists sd_test;\nDROP TABLE IF EXISTS sd_test.studies;";
+        assert_eq!(to_test_schemas(sql), expected);
+        assert_eq!(to_test_schemas("DROP SCHEMA IF EXISTS lup CASCADE;"), "DROP SCHEMA IF EXISTS lup_test CASCADE;");
+    }
+
+    #[test]
+    fn check_other_schemas_and_names_unchanged_in_test_sql() {
+        let sql = "select sd_sid, s.ad_id from ctx.org_names s where s.mnemonic = 'lup'";
+        assert_eq!(to_test_schemas(sql), sql);
+        assert_eq!(to_test_schemas("select to_regclass('ad.studies')"), "select to_regclass('ad_test.studies')");
+    }
+}